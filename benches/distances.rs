@@ -2,66 +2,104 @@ extern crate latus;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
-use latus::distances::angular::matrix_inner_product;
-use latus::distances::hyperbolic::half_plane_distance;
-use latus::distances::lp_norm::l2_distance;
+use latus::distances::angular::InnerProduct;
+use latus::distances::hyperbolic::HalfPlane;
+use latus::distances::kernels::{kernels, rowwise, scalar};
+use latus::distances::lp_norm::L2;
+use latus::distances::Distance;
 use latus::prelude::*;
 use latus::primitives::vector::random_vector;
-use latus::primitives::vector_table::VectorTable;
 
-fn inner_product_top_k(table: &mut VectorTable, vector: &Vector, k: usize) {
-    table.matrix_top_k_by_metric(&matrix_inner_product, vector, k, false);
+use ndarray::{stack, Axis};
+
+// The ndarray implementations that the kernels replaced,
+// kept here as a baseline for comparison
+
+fn inner_product_ndarray(a: &Vector, b: &Matrix) -> Vector {
+    b.dot(a)
 }
 
-fn l2_distance_bottom_k(table: &VectorTable, vector: &Vector, k: usize) {
-    table.bottom_k_by_metric(&l2_distance, vector, k);
+fn l2_ndarray(a: &Vector, b: &Matrix) -> Vector {
+    let sub = b - a;
+    sub.map_axis(Axis(1), |row| row.dot(&row).sqrt())
 }
 
-fn half_plane_dist_bottom_k(table: &VectorTable, vector: &Vector, k: usize) {
-    table.bottom_k_by_metric(&half_plane_distance, vector, k);
+fn half_plane_ndarray(a: &Vector, b: &Matrix) -> Vector {
+    let (a_x, a_view_y) = a.view().split_at(Axis(0), a.len_of(Axis(0)) - 1);
+    let (b_x, b_view_y) = b.view().split_at(Axis(1), b.len_of(Axis(1)) - 1);
+
+    let a_y = a_view_y.first().unwrap();
+    let b_y = b_view_y.index_axis(Axis(1), 0);
+
+    let y_diff = b_y.to_owned() - *a_y;
+    let y_diff_ref = b_y.to_owned() + *a_y;
+    let y_diff_sq = &y_diff * &y_diff;
+    let y_diff_ref_sq = &y_diff_ref * &y_diff_ref;
+
+    let b_x_sq: Vector = b_x
+        .axis_iter(Axis(0))
+        .map(|vector| vector.dot(&vector))
+        .collect();
+
+    let x_diff_sq = b_x_sq - 2. * &b_x.dot(&a_x) + a_x.dot(&a_x);
+
+    let diff_mag = (&x_diff_sq + &y_diff_sq).mapv(f32::sqrt);
+    let diff_mag_ref = (&x_diff_sq + &y_diff_ref_sq).mapv(f32::sqrt);
+
+    let numerator = diff_mag + diff_mag_ref;
+    let denominator = 2. * (&b_y * *a_y).mapv(f32::sqrt);
+    2. * (numerator / denominator).mapv(f32::ln)
 }
 
 fn distances_benchmark(c: &mut Criterion) {
-    let index_size = 1_048_576;
+    let num_vectors = 65_536;
     let dim = 128;
-    let k = 100;
 
-    let vectors: Vec<Vector> = (0..index_size).map(|_| random_vector(dim)).collect();
-
-    let mut vector_table = VectorTable::new(dim, false);
-    vector_table.insert_many(&vectors);
+    let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
+    let views: Vec<_> = vectors.iter().map(|v| v.view()).collect();
+    let matrix: Matrix = stack(Axis(0), &views).unwrap();
 
     let query_vector = random_vector(dim);
 
-    c.bench_function("inner_product top-k", |b| {
-        b.iter(|| {
-            inner_product_top_k(
-                black_box(&mut vector_table),
-                black_box(&query_vector),
-                black_box(k),
-            )
-        })
+    // Named after the instruction set picked at runtime
+    let simd = format!("simd_{:?}", kernels().instruction_set).to_lowercase();
+
+    let mut group = c.benchmark_group("inner_product");
+    group.bench_function("ndarray", |b| {
+        b.iter(|| inner_product_ndarray(black_box(&query_vector), black_box(&matrix)))
+    });
+    group.bench_function("scalar", |b| {
+        b.iter(|| rowwise(black_box(&query_vector), black_box(&matrix), scalar::dot))
     });
+    group.bench_function(&simd, |b| {
+        b.iter(|| InnerProduct {}.matrix_dist(black_box(&query_vector), black_box(&matrix)))
+    });
+    group.finish();
 
-    c.bench_function("l2_distance bottom-k", |b| {
+    let mut group = c.benchmark_group("l2");
+    group.bench_function("ndarray", |b| {
+        b.iter(|| l2_ndarray(black_box(&query_vector), black_box(&matrix)))
+    });
+    group.bench_function("scalar", |b| {
         b.iter(|| {
-            l2_distance_bottom_k(
-                black_box(&vector_table),
-                black_box(&query_vector),
-                black_box(k),
-            )
+            rowwise(black_box(&query_vector), black_box(&matrix), |a, b| {
+                scalar::l2_sq(a, b).sqrt()
+            })
         })
     });
+    group.bench_function(&simd, |b| {
+        b.iter(|| L2 {}.matrix_dist(black_box(&query_vector), black_box(&matrix)))
+    });
+    group.finish();
 
-    c.bench_function("half_plane_distance bottom-k", |b| {
-        b.iter(|| {
-            half_plane_dist_bottom_k(
-                black_box(&vector_table),
-                black_box(&query_vector),
-                black_box(k),
-            )
-        })
+    let mut group = c.benchmark_group("half_plane");
+    group.bench_function("ndarray", |b| {
+        b.iter(|| half_plane_ndarray(black_box(&query_vector), black_box(&matrix)))
+    });
+    group.bench_function(&simd, |b| {
+        b.iter(|| HalfPlane {}.matrix_dist(black_box(&query_vector), black_box(&matrix)))
     });
+    group.finish();
 }
 
 criterion_group!(benches, distances_benchmark);
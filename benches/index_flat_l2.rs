@@ -15,13 +15,13 @@ fn query_benchmark(c: &mut Criterion) {
     let dim = 100;
     let k = 10;
 
-    let mut index_l2: IndexFlatL2 = IndexFlatL2::new(dim);
+    let mut index_l2: IndexFlatL2 = IndexFlatL2::new(dim, false);
     // let mut index_l2_matrix: IndexFlatL2 = IndexFlatL2::new(dim, false);
     // let mut index_l2_chunked: IndexFlatL2 = IndexFlatL2::new(dim);
 
     for _ in 0..index_size {
         let vector = random_vector(dim);
-        index_l2.insert(vector.clone());
+        index_l2.insert(&vector);
         // index_l2_matrix.insert(&vector.clone());
         // index_l2_chunked.insert(&vector.clone());
     }
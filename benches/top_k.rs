@@ -0,0 +1,71 @@
+extern crate latus;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use latus::distances::angular::InnerProduct;
+use latus::distances::hyperbolic::HalfPlane;
+use latus::distances::lp_norm::L2;
+use latus::prelude::*;
+use latus::primitives::vector::random_vector;
+use latus::primitives::vector_table::VectorTable;
+
+// End-to-end top-k over a whole table, for comparing
+// the distance kernels against the ndarray baseline
+
+fn inner_product_top_k(table: &VectorTable, vector: &Vector, k: usize) {
+    table.matrix_top_k_by_metric(&InnerProduct {}, vector, k, false);
+}
+
+fn l2_distance_bottom_k(table: &VectorTable, vector: &Vector, k: usize) {
+    table.matrix_top_k_by_metric(&L2 {}, vector, k, true);
+}
+
+fn half_plane_dist_bottom_k(table: &VectorTable, vector: &Vector, k: usize) {
+    table.matrix_top_k_by_metric(&HalfPlane {}, vector, k, true);
+}
+
+fn top_k_benchmark(c: &mut Criterion) {
+    let index_size = 1_048_576;
+    let dim = 128;
+    let k = 100;
+
+    let vectors: Vec<Vector> = (0..index_size).map(|_| random_vector(dim)).collect();
+
+    let mut vector_table = VectorTable::new(dim, false);
+    vector_table.insert_many(&vectors);
+
+    let query_vector = random_vector(dim);
+
+    c.bench_function("inner_product top-k", |b| {
+        b.iter(|| {
+            inner_product_top_k(
+                black_box(&vector_table),
+                black_box(&query_vector),
+                black_box(k),
+            )
+        })
+    });
+
+    c.bench_function("l2_distance bottom-k", |b| {
+        b.iter(|| {
+            l2_distance_bottom_k(
+                black_box(&vector_table),
+                black_box(&query_vector),
+                black_box(k),
+            )
+        })
+    });
+
+    c.bench_function("half_plane_distance bottom-k", |b| {
+        b.iter(|| {
+            half_plane_dist_bottom_k(
+                black_box(&vector_table),
+                black_box(&query_vector),
+                black_box(k),
+            )
+        })
+    });
+}
+
+criterion_group!(benches, top_k_benchmark);
+criterion_main!(benches);
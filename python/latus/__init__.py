@@ -0,0 +1,3 @@
+from .latus import IndexFlatCustom, IndexFlatHP, IndexFlatIP, IndexFlatL2
+
+__all__ = ["IndexFlatCustom", "IndexFlatHP", "IndexFlatIP", "IndexFlatL2"]
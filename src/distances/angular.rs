@@ -1,3 +1,4 @@
+use crate::distances::kernels::{dot, pairwise, rowwise, rowwise_into};
 use crate::distances::Distance;
 use crate::prelude::*;
 
@@ -6,10 +7,14 @@ pub struct InnerProduct {}
 
 impl Distance for InnerProduct {
     fn vector_dist(&self, a: &Vector, b: &Vector) -> f32 {
-        a.dot(b)
+        pairwise(a, b, dot)
     }
 
     fn matrix_dist(&self, a: &Vector, b: &Matrix) -> Vector {
-        b.dot(a)
+        rowwise(a, b, dot)
+    }
+
+    fn matrix_dist_into(&self, a: &Vector, b: &Matrix, out: &mut [f32]) {
+        rowwise_into(a, b, out, |_, a, b| dot(a, b))
     }
 }
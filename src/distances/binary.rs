@@ -0,0 +1,60 @@
+use crate::distances::BinaryDistance;
+use crate::prelude::*;
+
+use ndarray::Axis;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Hamming {}
+
+impl BinaryDistance for Hamming {
+    fn vector_dist(&self, a: &BinaryVector, b: &BinaryVector) -> f32 {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a_word, b_word)| (a_word ^ b_word).count_ones())
+            .sum::<u32>() as f32
+    }
+
+    fn matrix_dist(&self, a: &BinaryVector, b: &BinaryMatrix) -> Vector {
+        b.axis_iter(Axis(0))
+            .map(|row| {
+                row.iter()
+                    .zip(a.iter())
+                    .map(|(b_word, a_word)| (a_word ^ b_word).count_ones())
+                    .sum::<u32>() as f32
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Jaccard {}
+
+impl Jaccard {
+    fn words_dist<'a>(a: impl Iterator<Item = &'a u64>, b: impl Iterator<Item = &'a u64>) -> f32 {
+        let (intersection, union) = a.zip(b).fold((0, 0), |(inter, uni), (a_word, b_word)| {
+            (
+                inter + (a_word & b_word).count_ones(),
+                uni + (a_word | b_word).count_ones(),
+            )
+        });
+
+        // Two empty sets are identical
+        if union == 0 {
+            0.
+        } else {
+            1. - intersection as f32 / union as f32
+        }
+    }
+}
+
+impl BinaryDistance for Jaccard {
+    fn vector_dist(&self, a: &BinaryVector, b: &BinaryVector) -> f32 {
+        Jaccard::words_dist(a.iter(), b.iter())
+    }
+
+    fn matrix_dist(&self, a: &BinaryVector, b: &BinaryMatrix) -> Vector {
+        b.axis_iter(Axis(0))
+            .map(|row| Jaccard::words_dist(a.iter(), row.iter()))
+            .collect()
+    }
+}
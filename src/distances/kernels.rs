@@ -0,0 +1,390 @@
+// Hand-vectorized distance kernels over contiguous f32 slices
+//
+// The widest instruction set available is detected once at runtime,
+// with a portable scalar fallback used everywhere else
+
+use crate::prelude::*;
+
+use std::sync::OnceLock;
+
+pub type Kernel = fn(&[f32], &[f32]) -> f32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionSet {
+    Scalar,
+    Avx2,
+    Avx512,
+    Neon,
+}
+
+#[derive(Debug)]
+pub struct Kernels {
+    pub instruction_set: InstructionSet,
+    pub dot: Kernel,
+    pub l2_sq: Kernel,
+}
+
+static KERNELS: OnceLock<Kernels> = OnceLock::new();
+
+pub fn kernels() -> &'static Kernels {
+    KERNELS.get_or_init(detect)
+}
+
+fn detect() -> Kernels {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx512f") {
+            return Kernels {
+                instruction_set: InstructionSet::Avx512,
+                dot: x86::dot_avx512_safe,
+                l2_sq: x86::l2_sq_avx512_safe,
+            };
+        }
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            return Kernels {
+                instruction_set: InstructionSet::Avx2,
+                dot: x86::dot_avx2_safe,
+                l2_sq: x86::l2_sq_avx2_safe,
+            };
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            return Kernels {
+                instruction_set: InstructionSet::Neon,
+                dot: neon::dot_neon_safe,
+                l2_sq: neon::l2_sq_neon_safe,
+            };
+        }
+    }
+
+    Kernels {
+        instruction_set: InstructionSet::Scalar,
+        dot: scalar::dot,
+        l2_sq: scalar::l2_sq,
+    }
+}
+
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    (kernels().dot)(a, b)
+}
+
+pub fn l2_sq(a: &[f32], b: &[f32]) -> f32 {
+    (kernels().l2_sq)(a, b)
+}
+
+// Squared L2 distance from ||a||^2 - 2 a.b + ||b||^2, given both squared
+// norms, so it costs a single dot product. Close rows cancel down to
+// rounding noise that way, so they're computed directly instead
+pub fn l2_sq_with_norms(a: &[f32], b: &[f32], a_sq: f32, b_sq: f32) -> f32 {
+    let expanded = a_sq - 2. * dot(a, b) + b_sq;
+    if expanded > CANCELLATION * (a_sq + b_sq) {
+        expanded
+    } else {
+        l2_sq(a, b)
+    }
+}
+
+// Fraction of the summed squared norms below which
+// the expansion is too imprecise to be used
+const CANCELLATION: f32 = 1e-2;
+
+// Applies a kernel to a pair of vectors, copying only if
+// one of them isn't laid out contiguously in memory
+pub fn pairwise(a: &Vector, b: &Vector, kernel: impl Fn(&[f32], &[f32]) -> f32) -> f32 {
+    match (a.as_slice(), b.as_slice()) {
+        (Some(a), Some(b)) => kernel(a, b),
+        _ => kernel(&a.to_vec(), &b.to_vec()),
+    }
+}
+
+// Applies a kernel between a vector and each row of a matrix
+pub fn rowwise(a: &Vector, b: &Matrix, kernel: impl Fn(&[f32], &[f32]) -> f32) -> Vector {
+    let mut out = Vector::zeros(b.nrows());
+    rowwise_into(a, b, out.as_slice_mut().unwrap(), |_, a, b| kernel(a, b));
+    out
+}
+
+// Applies a kernel between a vector and each row of a matrix, writing
+// the results into `out`. The kernel also receives the row index, so
+// it can look up cached per-row values
+pub fn rowwise_into(
+    a: &Vector,
+    b: &Matrix,
+    out: &mut [f32],
+    kernel: impl Fn(usize, &[f32], &[f32]) -> f32,
+) {
+    assert_eq!(out.len(), b.nrows(), "Output length doesn't match rows");
+
+    let a_owned;
+    let a = match a.as_slice() {
+        Some(a) => a,
+        None => {
+            a_owned = a.to_vec();
+            &a_owned
+        }
+    };
+
+    for (pos, (row, out)) in b.rows().into_iter().zip(out.iter_mut()).enumerate() {
+        *out = match row.as_slice() {
+            Some(row) => kernel(pos, a, row),
+            None => kernel(pos, a, &row.to_vec()),
+        };
+    }
+}
+
+pub mod scalar {
+    pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dims don't match");
+        a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+    }
+
+    pub fn l2_sq(a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dims don't match");
+        a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::scalar;
+    use std::arch::x86_64::*;
+
+    // The safe wrappers are only ever handed out by `detect`
+    // after the matching CPU features have been confirmed
+
+    pub fn dot_avx2_safe(a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dims don't match");
+        unsafe { dot_avx2(a, b) }
+    }
+
+    pub fn l2_sq_avx2_safe(a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dims don't match");
+        unsafe { l2_sq_avx2(a, b) }
+    }
+
+    pub fn dot_avx512_safe(a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dims don't match");
+        unsafe { dot_avx512(a, b) }
+    }
+
+    pub fn l2_sq_avx512_safe(a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dims don't match");
+        unsafe { l2_sq_avx512(a, b) }
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn hsum_avx2(v: __m256) -> f32 {
+        let sum = _mm_add_ps(_mm256_castps256_ps128(v), _mm256_extractf128_ps(v, 1));
+        let shuf = _mm_movehdup_ps(sum);
+        let sum = _mm_add_ps(sum, shuf);
+        let shuf = _mm_movehl_ps(shuf, sum);
+        _mm_cvtss_f32(_mm_add_ss(sum, shuf))
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn dot_avx2(a: &[f32], b: &[f32]) -> f32 {
+        let split = a.len() - a.len() % 16;
+        let (a_ptr, b_ptr) = (a.as_ptr(), b.as_ptr());
+
+        // Two accumulators to hide FMA latency
+        let mut acc0 = _mm256_setzero_ps();
+        let mut acc1 = _mm256_setzero_ps();
+        for i in (0..split).step_by(16) {
+            acc0 = _mm256_fmadd_ps(
+                _mm256_loadu_ps(a_ptr.add(i)),
+                _mm256_loadu_ps(b_ptr.add(i)),
+                acc0,
+            );
+            acc1 = _mm256_fmadd_ps(
+                _mm256_loadu_ps(a_ptr.add(i + 8)),
+                _mm256_loadu_ps(b_ptr.add(i + 8)),
+                acc1,
+            );
+        }
+
+        hsum_avx2(_mm256_add_ps(acc0, acc1)) + scalar::dot(&a[split..], &b[split..])
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn l2_sq_avx2(a: &[f32], b: &[f32]) -> f32 {
+        let split = a.len() - a.len() % 16;
+        let (a_ptr, b_ptr) = (a.as_ptr(), b.as_ptr());
+
+        let mut acc0 = _mm256_setzero_ps();
+        let mut acc1 = _mm256_setzero_ps();
+        for i in (0..split).step_by(16) {
+            let diff0 = _mm256_sub_ps(_mm256_loadu_ps(a_ptr.add(i)), _mm256_loadu_ps(b_ptr.add(i)));
+            let diff1 = _mm256_sub_ps(
+                _mm256_loadu_ps(a_ptr.add(i + 8)),
+                _mm256_loadu_ps(b_ptr.add(i + 8)),
+            );
+            acc0 = _mm256_fmadd_ps(diff0, diff0, acc0);
+            acc1 = _mm256_fmadd_ps(diff1, diff1, acc1);
+        }
+
+        hsum_avx2(_mm256_add_ps(acc0, acc1)) + scalar::l2_sq(&a[split..], &b[split..])
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn dot_avx512(a: &[f32], b: &[f32]) -> f32 {
+        let split = a.len() - a.len() % 32;
+        let (a_ptr, b_ptr) = (a.as_ptr(), b.as_ptr());
+
+        let mut acc0 = _mm512_setzero_ps();
+        let mut acc1 = _mm512_setzero_ps();
+        for i in (0..split).step_by(32) {
+            acc0 = _mm512_fmadd_ps(
+                _mm512_loadu_ps(a_ptr.add(i)),
+                _mm512_loadu_ps(b_ptr.add(i)),
+                acc0,
+            );
+            acc1 = _mm512_fmadd_ps(
+                _mm512_loadu_ps(a_ptr.add(i + 16)),
+                _mm512_loadu_ps(b_ptr.add(i + 16)),
+                acc1,
+            );
+        }
+
+        _mm512_reduce_add_ps(_mm512_add_ps(acc0, acc1)) + scalar::dot(&a[split..], &b[split..])
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn l2_sq_avx512(a: &[f32], b: &[f32]) -> f32 {
+        let split = a.len() - a.len() % 32;
+        let (a_ptr, b_ptr) = (a.as_ptr(), b.as_ptr());
+
+        let mut acc0 = _mm512_setzero_ps();
+        let mut acc1 = _mm512_setzero_ps();
+        for i in (0..split).step_by(32) {
+            let diff0 = _mm512_sub_ps(_mm512_loadu_ps(a_ptr.add(i)), _mm512_loadu_ps(b_ptr.add(i)));
+            let diff1 = _mm512_sub_ps(
+                _mm512_loadu_ps(a_ptr.add(i + 16)),
+                _mm512_loadu_ps(b_ptr.add(i + 16)),
+            );
+            acc0 = _mm512_fmadd_ps(diff0, diff0, acc0);
+            acc1 = _mm512_fmadd_ps(diff1, diff1, acc1);
+        }
+
+        _mm512_reduce_add_ps(_mm512_add_ps(acc0, acc1)) + scalar::l2_sq(&a[split..], &b[split..])
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::scalar;
+    use std::arch::aarch64::*;
+
+    pub fn dot_neon_safe(a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dims don't match");
+        unsafe { dot_neon(a, b) }
+    }
+
+    pub fn l2_sq_neon_safe(a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dims don't match");
+        unsafe { l2_sq_neon(a, b) }
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn dot_neon(a: &[f32], b: &[f32]) -> f32 {
+        let split = a.len() - a.len() % 8;
+        let (a_ptr, b_ptr) = (a.as_ptr(), b.as_ptr());
+
+        let mut acc0 = vdupq_n_f32(0.);
+        let mut acc1 = vdupq_n_f32(0.);
+        for i in (0..split).step_by(8) {
+            acc0 = vfmaq_f32(acc0, vld1q_f32(a_ptr.add(i)), vld1q_f32(b_ptr.add(i)));
+            acc1 = vfmaq_f32(
+                acc1,
+                vld1q_f32(a_ptr.add(i + 4)),
+                vld1q_f32(b_ptr.add(i + 4)),
+            );
+        }
+
+        vaddvq_f32(vaddq_f32(acc0, acc1)) + scalar::dot(&a[split..], &b[split..])
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn l2_sq_neon(a: &[f32], b: &[f32]) -> f32 {
+        let split = a.len() - a.len() % 8;
+        let (a_ptr, b_ptr) = (a.as_ptr(), b.as_ptr());
+
+        let mut acc0 = vdupq_n_f32(0.);
+        let mut acc1 = vdupq_n_f32(0.);
+        for i in (0..split).step_by(8) {
+            let diff0 = vsubq_f32(vld1q_f32(a_ptr.add(i)), vld1q_f32(b_ptr.add(i)));
+            let diff1 = vsubq_f32(vld1q_f32(a_ptr.add(i + 4)), vld1q_f32(b_ptr.add(i + 4)));
+            acc0 = vfmaq_f32(acc0, diff0, diff0);
+            acc1 = vfmaq_f32(acc1, diff1, diff1);
+        }
+
+        vaddvq_f32(vaddq_f32(acc0, acc1)) + scalar::l2_sq(&a[split..], &b[split..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect, scalar, InstructionSet};
+    use crate::primitives::vector::random_vector;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() <= 1e-4 * b.abs().max(1.), "{} != {}", a, b);
+    }
+
+    #[test]
+    fn detected_kernels_match_scalar() {
+        let kernels = detect();
+
+        // Cover the unrolled body, the scalar tail and empty inputs
+        for dim in [0, 1, 7, 16, 33, 100, 128, 257] {
+            let a = random_vector(dim).to_vec();
+            let b = random_vector(dim).to_vec();
+
+            assert_close((kernels.dot)(&a, &b), scalar::dot(&a, &b));
+            assert_close((kernels.l2_sq)(&a, &b), scalar::l2_sq(&a, &b));
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx2_kernels_match_scalar() {
+        // Exercised separately since AVX-512 wins dispatch where available
+        if !(is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma")) {
+            return;
+        }
+
+        for dim in [0, 1, 7, 16, 33, 100, 128, 257] {
+            let a = random_vector(dim).to_vec();
+            let b = random_vector(dim).to_vec();
+
+            assert_close(super::x86::dot_avx2_safe(&a, &b), scalar::dot(&a, &b));
+            assert_close(super::x86::l2_sq_avx2_safe(&a, &b), scalar::l2_sq(&a, &b));
+        }
+    }
+
+    #[test]
+    fn detects_instruction_set() {
+        let kernels = detect();
+
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        assert_eq!(kernels.instruction_set, InstructionSet::Scalar);
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(kernels.instruction_set, InstructionSet::Neon);
+
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx512f") {
+            assert_eq!(kernels.instruction_set, InstructionSet::Avx512);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Vector dims don't match")]
+    fn mismatched_dims() {
+        let a = random_vector(16).to_vec();
+        let b = random_vector(17).to_vec();
+        super::dot(&a, &b);
+    }
+}
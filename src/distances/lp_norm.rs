@@ -1,3 +1,4 @@
+use crate::distances::kernels::{dot, l2_sq, l2_sq_with_norms, pairwise, rowwise, rowwise_into};
 use crate::distances::Distance;
 use crate::prelude::*;
 
@@ -6,12 +7,27 @@ pub struct L2 {}
 
 impl Distance for L2 {
     fn vector_dist(&self, a: &Vector, b: &Vector) -> f32 {
-        let sub = b - a;
-        sub.dot(&sub).sqrt()
+        pairwise(a, b, |a, b| l2_sq(a, b).sqrt())
     }
 
     fn matrix_dist(&self, a: &Vector, b: &Matrix) -> Vector {
-        let sub = b - a;
-        sub.dot(&sub).iter().map(|s| s.sqrt()).collect()
+        rowwise(a, b, |a, b| l2_sq(a, b).sqrt())
+    }
+
+    fn matrix_dist_into(&self, a: &Vector, b: &Matrix, out: &mut [f32]) {
+        rowwise_into(a, b, out, |_, a, b| l2_sq(a, b).sqrt())
+    }
+
+    fn matrix_dist_with_norms_into(
+        &self,
+        a: &Vector,
+        b: &Matrix,
+        b_sq_norms: &Vector,
+        out: &mut [f32],
+    ) {
+        let a_sq = pairwise(a, a, dot);
+        rowwise_into(a, b, out, |pos, a, b| {
+            l2_sq_with_norms(a, b, a_sq, b_sq_norms[pos]).sqrt()
+        })
     }
 }
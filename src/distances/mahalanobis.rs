@@ -0,0 +1,156 @@
+use crate::distances::Distance;
+use crate::prelude::*;
+
+use ndarray::Axis;
+
+// Pivots below this are treated as zero, so positive
+// semi-definite metrics factor without failing
+const PIVOT_TOLERANCE: f32 = 1e-6;
+
+// Mahalanobis distance under a learned PSD matrix M = L L^T,
+// computed as the L2 distance between vectors mapped into the
+// whitened space by the lower-triangular Cholesky factor L
+#[derive(Debug, PartialEq)]
+pub struct Mahalanobis {
+    factor: Matrix,
+}
+
+impl Mahalanobis {
+    pub fn new(metric: &Matrix) -> Mahalanobis {
+        Mahalanobis {
+            factor: cholesky(metric),
+        }
+    }
+
+    pub fn from_cholesky(factor: Matrix) -> Mahalanobis {
+        assert!(factor.is_square(), "Cholesky factor isn't square");
+        Mahalanobis { factor }
+    }
+
+    pub fn dim(&self) -> usize {
+        self.factor.nrows()
+    }
+
+    pub fn factor(&self) -> &Matrix {
+        &self.factor
+    }
+
+    // Maps x to L^T x (as a row vector, x L)
+    pub fn transform(&self, vector: &Vector) -> Vector {
+        vector.dot(&self.factor)
+    }
+
+    pub fn transform_matrix(&self, matrix: &Matrix) -> Matrix {
+        matrix.dot(&self.factor)
+    }
+}
+
+impl Distance for Mahalanobis {
+    fn vector_dist(&self, a: &Vector, b: &Vector) -> f32 {
+        let sub = self.transform(&(b - a));
+        sub.dot(&sub).sqrt()
+    }
+
+    fn matrix_dist(&self, a: &Vector, b: &Matrix) -> Vector {
+        // One matrix product for the whole chunk, then L2 in the
+        // whitened space using ||b||^2 - 2 a.b + ||a||^2
+        let a_t = self.transform(a);
+        let b_t = self.transform_matrix(b);
+
+        let a_sq = a_t.dot(&a_t);
+        let b_sq = b_t.map_axis(Axis(1), |row| row.dot(&row));
+        let dot = b_t.dot(&a_t);
+
+        (b_sq - 2. * dot + a_sq).mapv(|d| d.max(0.).sqrt())
+    }
+}
+
+// Lower-triangular Cholesky factor of a symmetric PSD matrix
+pub fn cholesky(metric: &Matrix) -> Matrix {
+    assert!(metric.is_square(), "Metric matrix isn't square");
+
+    let dim = metric.nrows();
+    let mut factor = Matrix::zeros((dim, dim));
+
+    for j in 0..dim {
+        let mut pivot = metric[[j, j]];
+        for p in 0..j {
+            pivot -= factor[[j, p]] * factor[[j, p]];
+        }
+
+        assert!(
+            pivot > -PIVOT_TOLERANCE,
+            "Metric matrix isn't positive semi-definite"
+        );
+
+        if pivot <= PIVOT_TOLERANCE {
+            // Degenerate direction; leave the column at zero
+            continue;
+        }
+
+        let diag = pivot.sqrt();
+        factor[[j, j]] = diag;
+
+        for i in (j + 1)..dim {
+            let mut value = metric[[i, j]];
+            for p in 0..j {
+                value -= factor[[i, p]] * factor[[j, p]];
+            }
+            factor[[i, j]] = value / diag;
+        }
+    }
+
+    factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cholesky, Mahalanobis};
+    use crate::distances::Distance;
+    use crate::prelude::*;
+    use crate::primitives::vector::random_vector;
+
+    use ndarray::{arr1, arr2};
+
+    #[test]
+    fn cholesky_reconstructs_metric() {
+        let metric: Matrix = arr2(&[[4., 2., 0.], [2., 5., 1.], [0., 1., 3.]]);
+        let factor = cholesky(&metric);
+        let product = factor.dot(&factor.t());
+
+        for (a, b) in product.iter().zip(metric.iter()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn matches_quadratic_form() {
+        let metric: Matrix = arr2(&[[2., 0.5], [0.5, 1.]]);
+        let distance = Mahalanobis::new(&metric);
+
+        let a: Vector = arr1(&[1., 2.]);
+        let b: Vector = arr1(&[3., -1.]);
+        let sub = &a - &b;
+        let expected = sub.dot(&metric.dot(&sub)).sqrt();
+
+        assert!((distance.vector_dist(&a, &b) - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn matrix_dist_matches_vector_dist() {
+        let dim = 16;
+        let metric = Matrix::eye(dim) * 3.;
+        let distance = Mahalanobis::new(&metric);
+
+        let query = random_vector(dim);
+        let vectors: Vec<Vector> = (0..10).map(|_| random_vector(dim)).collect();
+        let views: Vec<_> = vectors.iter().map(|v| v.view()).collect();
+        let matrix = ndarray::stack(ndarray::Axis(0), &views).unwrap();
+
+        let results = distance.matrix_dist(&query, &matrix);
+
+        for (result, vector) in results.iter().zip(vectors.iter()) {
+            assert!((result - distance.vector_dist(&query, vector)).abs() < 1e-4);
+        }
+    }
+}
@@ -1,7 +1,10 @@
 pub mod angular;
 pub mod base;
+pub mod binary;
 pub mod hyperbolic;
+pub mod kernels;
 pub mod lp_norm;
+pub mod mahalanobis;
 
 use crate::prelude::*;
 
@@ -9,4 +12,31 @@ pub trait Distance {
     fn vector_dist(&self, a: &Vector, b: &Vector) -> f32;
 
     fn matrix_dist(&self, a: &Vector, b: &Matrix) -> Vector;
+
+    // Writes one result per row of `b` into `out` without allocating.
+    // The default falls back to `matrix_dist`, so it still allocates
+    fn matrix_dist_into(&self, a: &Vector, b: &Matrix, out: &mut [f32]) {
+        assert_eq!(out.len(), b.nrows(), "Output length doesn't match rows");
+        for (out, result) in out.iter_mut().zip(self.matrix_dist(a, b)) {
+            *out = result;
+        }
+    }
+
+    // Like `matrix_dist_into`, given the squared L2 norm of each row of `b`
+    // precomputed by the caller. Distances that can't use them ignore them
+    fn matrix_dist_with_norms_into(
+        &self,
+        a: &Vector,
+        b: &Matrix,
+        _b_sq_norms: &Vector,
+        out: &mut [f32],
+    ) {
+        self.matrix_dist_into(a, b, out)
+    }
+}
+
+pub trait BinaryDistance {
+    fn vector_dist(&self, a: &BinaryVector, b: &BinaryVector) -> f32;
+
+    fn matrix_dist(&self, a: &BinaryVector, b: &BinaryMatrix) -> Vector;
 }
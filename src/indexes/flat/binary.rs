@@ -0,0 +1,139 @@
+use crate::distances::binary::{Hamming, Jaccard};
+use crate::distances::BinaryDistance;
+use crate::prelude::*;
+use crate::primitives::binary_table::BinaryTable;
+use crate::primitives::filter::Filter;
+use crate::primitives::vector_table::Metric;
+
+pub type IndexFlatHamming = IndexFlatBinary<Hamming>;
+pub type IndexFlatJaccard = IndexFlatBinary<Jaccard>;
+
+#[derive(Debug, PartialEq)]
+pub struct IndexFlatBinary<D: BinaryDistance> {
+    pub table: BinaryTable,
+    distance: D,
+}
+
+impl<D: BinaryDistance + Default> IndexFlatBinary<D> {
+    pub fn new(dim: usize) -> IndexFlatBinary<D> {
+        IndexFlatBinary {
+            table: BinaryTable::new(dim),
+            distance: D::default(),
+        }
+    }
+
+    pub fn insert(&mut self, code: &BinaryVector) {
+        self.table.insert(code)
+    }
+
+    pub fn insert_many(&mut self, codes: &[BinaryVector]) {
+        self.table.insert_many(codes)
+    }
+
+    pub fn query(&self, code: &BinaryVector, k: usize) -> Vec<(Metric, usize)> {
+        self.table.top_k_by_metric(&self.distance, code, k, None)
+    }
+
+    pub fn query_filtered(
+        &self,
+        code: &BinaryVector,
+        k: usize,
+        filter: &Filter,
+    ) -> Vec<(Metric, usize)> {
+        self.table
+            .top_k_by_metric(&self.distance, code, k, Some(filter))
+    }
+
+    pub fn range_query(
+        &self,
+        code: &BinaryVector,
+        radius: f32,
+        filter: Option<&Filter>,
+        limit: Option<usize>,
+    ) -> Vec<(Metric, usize)> {
+        self.table
+            .range_by_metric(&self.distance, code, radius, filter, limit)
+    }
+
+    // See `FlatIndex::query_mmr`
+    pub fn query_mmr(
+        &self,
+        code: &BinaryVector,
+        k: usize,
+        fetch_k: usize,
+        lambda: f32,
+    ) -> Vec<(Metric, usize)> {
+        self.table
+            .mmr_top_k_by_metric(&self.distance, code, k, fetch_k, lambda)
+    }
+
+    pub fn query_many(&self, codes: &[BinaryVector], k: usize) -> Vec<Vec<(Metric, usize)>> {
+        let mut results = Vec::new();
+        for query_code in codes {
+            results.push(self.query(query_code, k))
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndexFlatHamming, IndexFlatJaccard};
+    use crate::prelude::*;
+    use crate::primitives::filter::Filter;
+    use crate::primitives::vector::random_binary_vector;
+
+    #[test]
+    fn insert_many() {
+        let dim = 256;
+        let num_vectors = 1000;
+
+        let codes: Vec<BinaryVector> = (0..num_vectors)
+            .map(|_| random_binary_vector(dim))
+            .collect();
+        let mut index = IndexFlatHamming::new(dim);
+        index.insert_many(&codes);
+
+        assert_eq!(index.table.len(), num_vectors);
+    }
+
+    #[test]
+    fn query() {
+        let dim = 256;
+        let num_vectors = 1000;
+        let k = 10;
+
+        let codes: Vec<BinaryVector> = (0..num_vectors)
+            .map(|_| random_binary_vector(dim))
+            .collect();
+        let mut index = IndexFlatHamming::new(dim);
+        index.insert_many(&codes);
+
+        let results = index.query(&codes[42], k);
+
+        assert_eq!(results.len(), k);
+        assert_eq!(results[0], (ordered_float::OrderedFloat(0.), 42));
+    }
+
+    #[test]
+    fn query_filtered() {
+        let dim = 256;
+        let num_vectors = 1000;
+        let k = 10;
+
+        let codes: Vec<BinaryVector> = (0..num_vectors)
+            .map(|_| random_binary_vector(dim))
+            .collect();
+        let mut index = IndexFlatJaccard::new(dim);
+        index.insert_many(&codes);
+
+        let mut filter = Filter::new();
+        filter.insert_many(&[3, 7, 11]);
+
+        let results = index.query_filtered(&codes[42], k, &filter);
+        let mut positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        positions.sort();
+
+        assert_eq!(positions, [3, 7, 11]);
+    }
+}
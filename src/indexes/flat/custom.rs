@@ -0,0 +1,88 @@
+use crate::distances::Distance;
+use crate::indexes::flat::FlatIndex;
+use crate::prelude::*;
+use crate::primitives::vector_table::{Metric, VectorTable};
+
+// Flat index over any distance implementation, for metrics
+// that don't (yet) have a dedicated index type
+#[derive(Debug, PartialEq)]
+pub struct IndexFlatCustom<D: Distance> {
+    pub table: VectorTable,
+    distance: D,
+    asc: bool,
+}
+
+impl<D: Distance> IndexFlatCustom<D> {
+    // `asc` is true when smaller values are closer (distances)
+    // and false when larger values are closer (similarities)
+    pub fn new(dim: usize, chunking: bool, distance: D, asc: bool) -> IndexFlatCustom<D> {
+        IndexFlatCustom {
+            table: VectorTable::new(dim, chunking),
+            distance,
+            asc,
+        }
+    }
+
+    pub fn distance(&self) -> &D {
+        &self.distance
+    }
+
+    pub fn insert(&mut self, vector: &Vector) {
+        self.table.insert(vector)
+    }
+
+    pub fn insert_many(&mut self, vectors: &[Vector]) {
+        self.table.insert_many(vectors)
+    }
+
+    pub fn matrix_query(&mut self, vector: &Vector, k: usize) -> Vec<(Metric, usize)> {
+        self.table
+            .matrix_top_k_by_metric(&self.distance, vector, k, self.asc)
+    }
+
+    pub fn query_many(&mut self, vectors: &[Vector], k: usize) -> Vec<Vec<(Metric, usize)>> {
+        let mut results = Vec::new();
+        for query_vector in vectors {
+            results.push(self.matrix_query(query_vector, k))
+        }
+        results
+    }
+}
+
+impl<D: Distance> FlatIndex for IndexFlatCustom<D> {
+    fn table(&self) -> &VectorTable {
+        &self.table
+    }
+
+    fn distance(&self) -> &dyn Distance {
+        &self.distance
+    }
+
+    fn asc(&self) -> bool {
+        self.asc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndexFlatCustom, Vector};
+    use crate::distances::lp_norm::L2;
+    use crate::primitives::vector::random_vector;
+
+    #[test]
+    fn matrix_query() {
+        let dim = 128;
+        let num_vectors = 1000;
+        let k = 10;
+
+        let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
+        let mut index = IndexFlatCustom::new(dim, true, L2 {}, true);
+        index.insert_many(&vectors);
+
+        let results = index.matrix_query(&vectors[42], k);
+        let nearest = results.iter().min().unwrap();
+
+        assert_eq!(results.len(), k);
+        assert_eq!(nearest.1, 42);
+    }
+}
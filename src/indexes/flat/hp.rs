@@ -1,9 +1,8 @@
 use crate::distances::hyperbolic::HalfPlane;
 use crate::distances::Distance;
+use crate::indexes::flat::FlatIndex;
 use crate::prelude::*;
-use crate::primitives::vector_table::{Metric, VectorTable};
-
-// TODO: Refactor this and IndexFlatL2 to implement trait(s)
+use crate::primitives::vector_table::{Metric, Precision, VectorTable};
 
 #[derive(Debug, PartialEq)]
 pub struct IndexFlatHP {
@@ -19,6 +18,13 @@ impl IndexFlatHP {
         }
     }
 
+    pub fn with_precision(dim: usize, precision: Precision) -> IndexFlatHP {
+        IndexFlatHP {
+            table: VectorTable::with_precision(dim, precision),
+            distance: HalfPlane {},
+        }
+    }
+
     pub fn insert(&mut self, vector: &Vector) {
         self.table.insert(vector)
     }
@@ -28,7 +34,7 @@ impl IndexFlatHP {
     }
 
     pub fn query(&mut self, vector: &Vector, k: usize) -> Vec<(Metric, usize)> {
-        self.table.top_k_by_metric(&self.distance, vector, k)
+        self.table.bottom_k_by_metric(&self.distance, vector, k)
     }
 
     pub fn matrix_query(&mut self, vector: &Vector, k: usize) -> Vec<(Metric, usize)> {
@@ -45,6 +51,20 @@ impl IndexFlatHP {
     }
 }
 
+impl FlatIndex for IndexFlatHP {
+    fn table(&self) -> &VectorTable {
+        &self.table
+    }
+
+    fn distance(&self) -> &dyn Distance {
+        &self.distance
+    }
+
+    fn asc(&self) -> bool {
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{IndexFlatHP, Vector};
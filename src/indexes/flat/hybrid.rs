@@ -0,0 +1,181 @@
+use crate::distances::angular::InnerProduct;
+use crate::prelude::*;
+use crate::primitives::filter::Filter;
+use crate::primitives::sparse_table::SparseTable;
+use crate::primitives::sparse_vector::SparseVector;
+use crate::primitives::vector_table::{push_bounded, Metric, ScanBuffer, VectorTable};
+
+use std::collections::HashMap;
+
+use min_max_heap::MinMaxHeap;
+use ordered_float::OrderedFloat;
+
+// How dense and sparse scores are combined into one ranking
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fusion {
+    // Weighted sum of the raw inner products, over every row
+    WeightedSum { dense: f32, sparse: f32 },
+    // Sum of 1 / (rank_constant + rank) over the top `depth` rows of each
+    // ranking, which ignores score scales. 60 is the usual rank constant
+    ReciprocalRank { rank_constant: f32, depth: usize },
+}
+
+// Each row has a dense embedding and a sparse term-weight vector (such
+// as BM25 weights), both scored by inner product against the query
+#[derive(Debug)]
+pub struct IndexFlatHybrid {
+    pub table: VectorTable,
+    pub sparse: SparseTable,
+    distance: InnerProduct,
+}
+
+impl IndexFlatHybrid {
+    pub fn new(dim: usize, chunking: bool) -> IndexFlatHybrid {
+        IndexFlatHybrid {
+            table: VectorTable::new(dim, chunking),
+            sparse: SparseTable::new(),
+            distance: InnerProduct {},
+        }
+    }
+
+    pub fn insert(&mut self, vector: &Vector, terms: &SparseVector) {
+        self.table.insert(vector);
+        self.sparse.insert(terms);
+    }
+
+    pub fn insert_many(&mut self, vectors: &[Vector], terms: &[SparseVector]) {
+        assert!(
+            vectors.len() == terms.len(),
+            "Need one sparse vector per dense vector"
+        );
+        self.table.insert_many(vectors);
+        self.sparse.insert_many(terms);
+    }
+
+    pub fn query(
+        &self,
+        vector: &Vector,
+        terms: &SparseVector,
+        k: usize,
+        fusion: Fusion,
+        filter: Option<&Filter>,
+    ) -> Vec<(Metric, usize)> {
+        match fusion {
+            Fusion::WeightedSum { dense, sparse } => {
+                let sparse_scores = self.sparse.inner_products(terms, filter);
+                let mut heap = MinMaxHeap::with_capacity(k);
+                let mut buffer = ScanBuffer::default();
+
+                self.table
+                    .scan(&self.distance, vector, &mut buffer, |metric, pos| {
+                        if filter.is_some_and(|filter| !filter.contains(pos as u32)) {
+                            return;
+                        }
+                        let sparse_score = sparse_scores.get(&pos).copied().unwrap_or(0.);
+                        let score = dense * metric.0 + sparse * sparse_score;
+                        push_bounded(&mut heap, k, false, (OrderedFloat(score), pos));
+                    });
+
+                heap.into_vec_desc()
+            }
+            Fusion::ReciprocalRank {
+                rank_constant,
+                depth,
+            } => {
+                let dense_results = self.table.top_k_after_by_metric(
+                    &self.distance,
+                    vector,
+                    depth,
+                    false,
+                    filter,
+                    None,
+                );
+                let sparse_results = self.sparse.top_k_inner_product(terms, depth, filter);
+
+                let mut fused: HashMap<usize, f32> = HashMap::new();
+                for results in [dense_results, sparse_results] {
+                    for (rank, (_, pos)) in results.iter().enumerate() {
+                        *fused.entry(*pos).or_insert(0.) += 1. / (rank_constant + rank as f32 + 1.);
+                    }
+                }
+
+                let mut heap = MinMaxHeap::with_capacity(k);
+                for (pos, score) in fused {
+                    push_bounded(&mut heap, k, false, (OrderedFloat(score), pos));
+                }
+                heap.into_vec_desc()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fusion, IndexFlatHybrid};
+    use crate::prelude::*;
+    use crate::primitives::filter::Filter;
+    use crate::primitives::sparse_vector::SparseVector;
+    use crate::primitives::vector::random_vector;
+    use ndarray::arr1;
+
+    #[test]
+    fn query() {
+        let dim = 16;
+        let num_vectors = 1000;
+        let k = 10;
+
+        let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
+        let terms: Vec<SparseVector> = (0..num_vectors)
+            .map(|pos| SparseVector::new(&[((pos % 50) as u32, 1.), (1000, 0.1)]))
+            .collect();
+
+        let mut index = IndexFlatHybrid::new(dim, true);
+        index.insert_many(&vectors, &terms);
+
+        let query = random_vector(dim);
+        let query_terms = SparseVector::new(&[(7, 100.)]);
+
+        // A heavy keyword weight pulls every row with the term to the top
+        let fusion = Fusion::WeightedSum {
+            dense: 1.,
+            sparse: 1.,
+        };
+        let results = index.query(&query, &query_terms, k, fusion, None);
+        assert_eq!(results.len(), k);
+        assert!(results.iter().all(|r| r.1 % 50 == 7));
+
+        let fusion = Fusion::ReciprocalRank {
+            rank_constant: 60.,
+            depth: 100,
+        };
+        let mut filter = Filter::new();
+        filter.insert_many(&[3, 57, 107]);
+        let results = index.query(&query, &query_terms, k, fusion, Some(&filter));
+        let mut positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        positions.sort();
+        assert_eq!(positions, [3, 57, 107]);
+    }
+
+    #[test]
+    fn query_reciprocal_rank() {
+        let mut index = IndexFlatHybrid::new(2, true);
+        index.insert_many(
+            &[arr1(&[1., 0.]), arr1(&[0.9, 0.]), arr1(&[0., 1.])],
+            &[
+                SparseVector::default(),
+                SparseVector::new(&[(7, 2.)]),
+                SparseVector::new(&[(7, 1.)]),
+            ],
+        );
+
+        // Rows found by both rankings come before the best dense-only row
+        let fusion = Fusion::ReciprocalRank {
+            rank_constant: 60.,
+            depth: 10,
+        };
+        let query_terms = SparseVector::new(&[(7, 1.)]);
+        let results = index.query(&arr1(&[1., 0.]), &query_terms, 3, fusion, None);
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        assert_eq!(positions, [1, 2, 0]);
+    }
+}
@@ -1,8 +1,11 @@
 use crate::distances::angular::InnerProduct;
+use crate::distances::Distance;
+use crate::indexes::flat::FlatIndex;
 use crate::prelude::*;
-use crate::primitives::vector_table::{Metric, VectorTable};
+use crate::primitives::aggregation::Aggregation;
+use crate::primitives::vector_table::{Metric, Precision, QueryBuffer, VectorTable};
 
-// TODO: Refactor this and IndexFlatL2 to implement trait(s)
+use ndarray::Axis;
 
 #[derive(Debug, PartialEq)]
 pub struct IndexFlatIP {
@@ -18,6 +21,13 @@ impl IndexFlatIP {
         }
     }
 
+    pub fn with_precision(dim: usize, precision: Precision) -> IndexFlatIP {
+        IndexFlatIP {
+            table: VectorTable::with_precision(dim, precision),
+            distance: InnerProduct {},
+        }
+    }
+
     pub fn insert(&mut self, vector: &Vector) {
         self.table.insert(vector)
     }
@@ -44,9 +54,52 @@ impl IndexFlatIP {
     }
 }
 
+impl FlatIndex for IndexFlatIP {
+    fn table(&self) -> &VectorTable {
+        &self.table
+    }
+
+    fn distance(&self) -> &dyn Distance {
+        &self.distance
+    }
+
+    fn asc(&self) -> bool {
+        false
+    }
+
+    fn query_multi(
+        &self,
+        vectors: &[Vector],
+        k: usize,
+        aggregation: Aggregation,
+    ) -> Vec<(Metric, usize)> {
+        assert!(!vectors.is_empty(), "Need at least one query vector");
+
+        // Inner products are linear, so the sum (or mean) of a row's products
+        // with each query is its product with the sum (or mean) of the queries
+        if let Aggregation::Sum | Aggregation::Mean = aggregation {
+            let views: Vec<_> = vectors.iter().map(|v| v.view()).collect();
+            let stacked = ndarray::stack(Axis(0), &views).expect("couldn't stack query vectors");
+            let centroid = match aggregation {
+                Aggregation::Sum => stacked.sum_axis(Axis(0)),
+                _ => stacked.mean_axis(Axis(0)).unwrap(),
+            };
+
+            let mut buffer = QueryBuffer::new();
+            self.matrix_query_into(&centroid, k, &mut buffer);
+            return buffer.results;
+        }
+
+        self.table
+            .multi_top_k_by_metric(&self.distance, vectors, k, false, aggregation)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{IndexFlatIP, Vector};
+    use super::{FlatIndex, IndexFlatIP, Vector};
+    use crate::distances::angular::InnerProduct;
+    use crate::primitives::aggregation::Aggregation;
     use crate::primitives::vector::random_vector;
 
     #[test]
@@ -72,4 +125,40 @@ mod tests {
         let query_vector = random_vector(dim);
         let result = index.query(&query_vector, k);
     }
+
+    #[test]
+    fn query_multi_centroid() {
+        let dim = 32;
+        let k = 10;
+
+        let vectors: Vec<Vector> = (0..5000).map(|_| random_vector(dim)).collect();
+        let queries: Vec<Vector> = (0..4).map(|_| random_vector(dim)).collect();
+        let mut index: IndexFlatIP = IndexFlatIP::new(dim, true);
+        index.insert_many(&vectors);
+
+        for aggregation in [Aggregation::Sum, Aggregation::Mean] {
+            let expected = index.table.multi_top_k_by_metric(
+                &InnerProduct {},
+                &queries,
+                k,
+                false,
+                aggregation,
+            );
+            let results = index.query_multi(&queries, k, aggregation);
+
+            assert_eq!(results.len(), expected.len());
+            for (result, expected) in results.iter().zip(expected.iter()) {
+                assert_eq!(result.1, expected.1);
+                assert!((result.0 .0 - expected.0 .0).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Need at least one query vector")]
+    fn query_multi_empty() {
+        let mut index: IndexFlatIP = IndexFlatIP::new(8, false);
+        index.insert_many(&[random_vector(8)]);
+        index.query_multi(&[], 1, Aggregation::Sum);
+    }
 }
@@ -0,0 +1,174 @@
+use crate::distances::angular::InnerProduct;
+use crate::distances::Distance;
+use crate::prelude::*;
+use crate::primitives::filter::Filter;
+use crate::primitives::quantized_table::QuantizedTable;
+use crate::primitives::quantizer::ScalarQuantizer;
+use crate::primitives::vector_file::VectorFile;
+use crate::primitives::vector_table::Metric;
+
+use std::path::Path;
+
+use ordered_float::OrderedFloat;
+
+// Inner product index over scalar-quantized vectors, which can
+// optionally keep exact vectors on disk for re-ranking
+#[derive(Debug)]
+pub struct IndexFlatIPSQ {
+    pub table: QuantizedTable,
+    exact: Option<VectorFile>,
+    distance: InnerProduct,
+}
+
+impl IndexFlatIPSQ {
+    pub fn new(quantizer: ScalarQuantizer) -> IndexFlatIPSQ {
+        IndexFlatIPSQ {
+            table: QuantizedTable::new(quantizer),
+            exact: None,
+            distance: InnerProduct {},
+        }
+    }
+
+    // Also writes exact vectors to `path`, so they can be used by `query_reranked`
+    pub fn with_rerank(quantizer: ScalarQuantizer, path: &Path) -> IndexFlatIPSQ {
+        let dim = quantizer.dim();
+        IndexFlatIPSQ {
+            exact: Some(VectorFile::create(path, dim)),
+            ..IndexFlatIPSQ::new(quantizer)
+        }
+    }
+
+    pub fn insert(&mut self, vector: &Vector) {
+        self.table.insert(vector);
+        if let Some(exact) = &mut self.exact {
+            exact.append(vector);
+        }
+    }
+
+    pub fn insert_many(&mut self, vectors: &[Vector]) {
+        self.table.insert_many(vectors);
+        if let Some(exact) = &mut self.exact {
+            for vector in vectors {
+                exact.append(vector);
+            }
+        }
+    }
+
+    pub fn query(&self, vector: &Vector, k: usize) -> Vec<(Metric, usize)> {
+        self.table.top_k_inner_product(vector, k)
+    }
+
+    // Retrieves `candidates` results by quantized score, then re-scores
+    // them against the exact vectors and keeps the best k
+    pub fn query_reranked(
+        &mut self,
+        vector: &Vector,
+        k: usize,
+        candidates: usize,
+    ) -> Vec<(Metric, usize)> {
+        let exact = self
+            .exact
+            .as_mut()
+            .expect("index wasn't created with exact vectors for re-ranking");
+
+        let mut results: Vec<(Metric, usize)> = self
+            .table
+            .top_k_inner_product(vector, candidates.max(k))
+            .iter()
+            .map(|(_, pos)| {
+                let score = self.distance.vector_dist(vector, &exact.read(*pos));
+                (OrderedFloat(score), *pos)
+            })
+            .collect();
+
+        results.sort_by(|a, b| b.cmp(a));
+        results.truncate(k);
+        results
+    }
+
+    // Rows whose approximate inner product is at least `radius`
+    pub fn range_query(
+        &self,
+        vector: &Vector,
+        radius: f32,
+        filter: Option<&Filter>,
+        limit: Option<usize>,
+    ) -> Vec<(Metric, usize)> {
+        self.table
+            .range_inner_product(vector, radius, filter, limit)
+    }
+
+    // See `FlatIndex::query_mmr`
+    pub fn query_mmr(
+        &self,
+        vector: &Vector,
+        k: usize,
+        fetch_k: usize,
+        lambda: f32,
+    ) -> Vec<(Metric, usize)> {
+        self.table
+            .mmr_top_k_inner_product(vector, k, fetch_k, lambda)
+    }
+
+    pub fn query_many(&self, vectors: &[Vector], k: usize) -> Vec<Vec<(Metric, usize)>> {
+        let mut results = Vec::new();
+        for query_vector in vectors {
+            results.push(self.query(query_vector, k))
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndexFlatIPSQ, Vector};
+    use crate::primitives::quantizer::{QuantizerBits, ScalarQuantizer};
+    use crate::primitives::vector::random_vector;
+
+    #[test]
+    fn query() {
+        let dim = 128;
+        let num_vectors = 1000;
+        let k = 10;
+
+        let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
+        let quantizer = ScalarQuantizer::train(&vectors, QuantizerBits::Eight);
+
+        let mut index = IndexFlatIPSQ::new(quantizer);
+        index.insert_many(&vectors);
+
+        let query_vector = random_vector(dim);
+        let results = index.query(&query_vector, k);
+
+        assert_eq!(results.len(), k);
+    }
+
+    #[test]
+    fn query_reranked() {
+        let dim = 128;
+        let num_vectors = 1000;
+        let k = 10;
+
+        let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
+        let quantizer = ScalarQuantizer::train(&vectors, QuantizerBits::Four);
+
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        let mut index = IndexFlatIPSQ::with_rerank(quantizer, tmpfile.path());
+        index.insert_many(&vectors);
+
+        let query_vector = random_vector(dim);
+        let results = index.query_reranked(&query_vector, k, 100);
+
+        let mut expected: Vec<(f32, usize)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(pos, v)| (query_vector.dot(v), pos))
+            .collect();
+        expected.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+        // Re-ranked scores are exact
+        assert_eq!(results.len(), k);
+        assert_eq!(results[0].1, expected[0].1);
+        assert!((results[0].0 .0 - expected[0].0).abs() < 1e-4);
+    }
+}
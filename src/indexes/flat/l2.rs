@@ -1,6 +1,8 @@
 use crate::distances::lp_norm::L2;
+use crate::distances::Distance;
+use crate::indexes::flat::FlatIndex;
 use crate::prelude::*;
-use crate::primitives::vector_table::{Metric, VectorTable};
+use crate::primitives::vector_table::{Metric, Precision, VectorTable};
 
 #[derive(Debug, PartialEq)]
 pub struct IndexFlatL2 {
@@ -9,15 +11,22 @@ pub struct IndexFlatL2 {
 }
 
 impl IndexFlatL2 {
-    pub fn new(dim: usize) -> IndexFlatL2 {
+    pub fn new(dim: usize, chunking: bool) -> IndexFlatL2 {
         IndexFlatL2 {
-            table: VectorTable::new(dim, false),
+            table: VectorTable::new(dim, chunking),
             distance: L2 {},
         }
     }
 
-    pub fn insert(&mut self, vector: Vector) {
-        self.table.insert(&vector)
+    pub fn with_precision(dim: usize, precision: Precision) -> IndexFlatL2 {
+        IndexFlatL2 {
+            table: VectorTable::with_precision(dim, precision),
+            distance: L2 {},
+        }
+    }
+
+    pub fn insert(&mut self, vector: &Vector) {
+        self.table.insert(vector)
     }
 
     pub fn insert_many(&mut self, vectors: &[Vector]) {
@@ -42,6 +51,20 @@ impl IndexFlatL2 {
     }
 }
 
+impl FlatIndex for IndexFlatL2 {
+    fn table(&self) -> &VectorTable {
+        &self.table
+    }
+
+    fn distance(&self) -> &dyn Distance {
+        &self.distance
+    }
+
+    fn asc(&self) -> bool {
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{IndexFlatL2, Vector};
@@ -53,7 +76,7 @@ mod tests {
         let dim = 128;
 
         let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
-        let mut index: IndexFlatL2 = IndexFlatL2::new(dim);
+        let mut index: IndexFlatL2 = IndexFlatL2::new(dim, false);
         index.insert_many(&vectors);
     }
 
@@ -64,7 +87,7 @@ mod tests {
         let k = 10;
 
         let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
-        let mut index: IndexFlatL2 = IndexFlatL2::new(dim);
+        let mut index: IndexFlatL2 = IndexFlatL2::new(dim, false);
         index.insert_many(&vectors);
 
         let query_vector = random_vector(dim);
@@ -0,0 +1,133 @@
+use crate::distances::lp_norm::L2;
+use crate::distances::mahalanobis::Mahalanobis;
+use crate::distances::Distance;
+use crate::indexes::flat::FlatIndex;
+use crate::prelude::*;
+use crate::primitives::vector_table::{Metric, VectorTable};
+
+use std::borrow::Cow;
+// Stores vectors already mapped into the whitened space, so
+// queries only transform the query vector and run plain L2
+#[derive(Debug, PartialEq)]
+pub struct IndexFlatMahalanobis {
+    pub table: VectorTable,
+    metric: Mahalanobis,
+    distance: L2,
+}
+
+impl IndexFlatMahalanobis {
+    pub fn new(metric: &Matrix, chunking: bool) -> IndexFlatMahalanobis {
+        IndexFlatMahalanobis::from_distance(Mahalanobis::new(metric), chunking)
+    }
+
+    pub fn from_cholesky(factor: Matrix, chunking: bool) -> IndexFlatMahalanobis {
+        IndexFlatMahalanobis::from_distance(Mahalanobis::from_cholesky(factor), chunking)
+    }
+
+    fn from_distance(metric: Mahalanobis, chunking: bool) -> IndexFlatMahalanobis {
+        IndexFlatMahalanobis {
+            table: VectorTable::new(metric.dim(), chunking),
+            metric,
+            distance: L2 {},
+        }
+    }
+
+    pub fn insert(&mut self, vector: &Vector) {
+        self.table.insert(&self.metric.transform(vector))
+    }
+
+    pub fn insert_many(&mut self, vectors: &[Vector]) {
+        let transformed: Vec<Vector> = vectors.iter().map(|v| self.metric.transform(v)).collect();
+        self.table.insert_many(&transformed)
+    }
+
+    pub fn query(&self, vector: &Vector, k: usize) -> Vec<(Metric, usize)> {
+        let transformed = self.metric.transform(vector);
+        self.table
+            .top_k_after_by_metric(&self.distance, &transformed, k, true, None, None)
+    }
+
+    pub fn matrix_query(&mut self, vector: &Vector, k: usize) -> Vec<(Metric, usize)> {
+        let transformed = self.metric.transform(vector);
+        self.table
+            .matrix_top_k_by_metric(&self.distance, &transformed, k, true)
+    }
+
+    pub fn query_many(&self, vectors: &[Vector], k: usize) -> Vec<Vec<(Metric, usize)>> {
+        let mut results = Vec::new();
+        for query_vector in vectors {
+            results.push(self.query(query_vector, k))
+        }
+        results
+    }
+}
+
+impl FlatIndex for IndexFlatMahalanobis {
+    fn table(&self) -> &VectorTable {
+        &self.table
+    }
+
+    fn distance(&self) -> &dyn Distance {
+        &self.distance
+    }
+
+    fn asc(&self) -> bool {
+        true
+    }
+
+    fn prepare<'a>(&self, vector: &'a Vector) -> Cow<'a, Vector> {
+        Cow::Owned(self.metric.transform(vector))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndexFlatMahalanobis, Matrix, Vector};
+    use crate::primitives::vector::random_vector;
+    use crate::primitives::vector_table::CHUNK_SIZE;
+
+    #[test]
+    fn insert_many() {
+        let dim = 32;
+        let num_vectors = 1000;
+
+        let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
+        let mut index = IndexFlatMahalanobis::new(&Matrix::eye(dim), false);
+        index.insert_many(&vectors);
+    }
+
+    #[test]
+    fn query() {
+        let dim = 8;
+        let num_vectors = CHUNK_SIZE + 100;
+        let k = 10;
+
+        let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
+        let mut index = IndexFlatMahalanobis::new(&(Matrix::eye(dim) * 2.), true);
+        index.insert_many(&vectors);
+
+        // Row 42 lives in a full chunk, not the tail
+        let results = index.query(&vectors[42], k);
+
+        assert_eq!(results.len(), k);
+        assert_eq!(results[0].1, 42);
+        assert!(results.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    }
+
+    #[test]
+    fn matrix_query() {
+        let dim = 32;
+        let num_vectors = 1000;
+        let k = 10;
+
+        let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
+        let mut index = IndexFlatMahalanobis::new(&(Matrix::eye(dim) * 2.), true);
+        index.insert_many(&vectors);
+
+        let results = index.matrix_query(&vectors[42], k);
+        let nearest = results.iter().min().unwrap();
+
+        assert_eq!(results.len(), k);
+        assert_eq!(nearest.1, 42);
+    }
+}
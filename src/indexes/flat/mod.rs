@@ -1,3 +1,212 @@
+pub mod binary;
+pub mod custom;
 pub mod hp;
+pub mod hybrid;
 pub mod ip;
+pub mod ip_sq;
 pub mod l2;
+pub mod mahalanobis;
+
+use crate::distances::Distance;
+use crate::prelude::*;
+use crate::primitives::aggregation::Aggregation;
+use crate::primitives::attributes::Attribute;
+use crate::primitives::exploration::Exploration;
+use crate::primitives::filter::Filter;
+use crate::primitives::planner::{QueryPlanner, QueryStats};
+use crate::primitives::vector_table::{Metric, QueryBuffer, ResultPages, VectorTable};
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+// Queries shared by the flat indexes that score a VectorTable directly.
+// An index only says how its table is scored, and gets these on top
+pub trait FlatIndex {
+    fn table(&self) -> &VectorTable;
+
+    fn distance(&self) -> &dyn Distance;
+
+    // True when smaller values are closer (distances)
+    // and false when larger values are closer (similarities)
+    fn asc(&self) -> bool;
+
+    // Maps a query into the space the table's rows are stored in
+    fn prepare<'a>(&self, vector: &'a Vector) -> Cow<'a, Vector> {
+        Cow::Borrowed(vector)
+    }
+
+    // Leaves the top k results in `buffer.results`, best first. Reusing the
+    // same buffer across queries avoids any allocation once it has grown
+    fn matrix_query_into(&self, vector: &Vector, k: usize, buffer: &mut QueryBuffer) {
+        self.table().matrix_top_k_by_metric_into(
+            self.distance(),
+            &self.prepare(vector),
+            k,
+            self.asc(),
+            buffer,
+        )
+    }
+
+    // Rows within `radius` of the query, best first. For similarities,
+    // rows scoring at least `radius` match
+    fn range_query(
+        &self,
+        vector: &Vector,
+        radius: f32,
+        filter: Option<&Filter>,
+        limit: Option<usize>,
+    ) -> Vec<(Metric, usize)> {
+        self.table().range_by_metric(
+            self.distance(),
+            &self.prepare(vector),
+            radius,
+            self.asc(),
+            filter,
+            limit,
+        )
+    }
+
+    // Best k_per_group rows for each value of the attribute, in one pass
+    fn query_grouped(
+        &self,
+        vector: &Vector,
+        k_per_group: usize,
+        attribute: &Attribute,
+    ) -> HashMap<String, Vec<(Metric, usize)>> {
+        self.table().grouped_top_k_by_metric(
+            self.distance(),
+            &self.prepare(vector),
+            k_per_group,
+            self.asc(),
+            attribute,
+        )
+    }
+
+    // Top k results in the filter, with counts of the rows in the filter
+    // (and within the radius, if given) that have each value of the attribute
+    fn query_faceted(
+        &self,
+        vector: &Vector,
+        k: usize,
+        filter: Option<&Filter>,
+        radius: Option<f32>,
+        attribute: &Attribute,
+    ) -> (Vec<(Metric, usize)>, BTreeMap<String, u64>) {
+        self.table().faceted_top_k_by_metric(
+            self.distance(),
+            &self.prepare(vector),
+            k,
+            self.asc(),
+            filter,
+            radius,
+            attribute,
+        )
+    }
+
+    // Picks k of the best `fetch_k` results by maximal marginal relevance,
+    // trading closeness to the query for diversity as `lambda` drops from 1
+    fn query_mmr(
+        &self,
+        vector: &Vector,
+        k: usize,
+        fetch_k: usize,
+        lambda: f32,
+    ) -> Vec<(Metric, usize)> {
+        self.table().mmr_top_k_by_metric(
+            self.distance(),
+            &self.prepare(vector),
+            k,
+            fetch_k,
+            lambda,
+            self.asc(),
+        )
+    }
+
+    // Top k results in the filter with some randomness mixed in, for
+    // exploring beyond the strict nearest neighbors. Seeded for reproducibility
+    fn query_explore(
+        &self,
+        vector: &Vector,
+        k: usize,
+        filter: Option<&Filter>,
+        exploration: Exploration,
+        seed: u64,
+    ) -> Vec<(Metric, usize)> {
+        self.table().explore_top_k_by_metric(
+            self.distance(),
+            &self.prepare(vector),
+            k,
+            self.asc(),
+            filter,
+            exploration,
+            seed,
+        )
+    }
+
+    // Top k results after the cursor, for fetching the page
+    // following one that ended with the cursor
+    fn query_after(
+        &self,
+        vector: &Vector,
+        k: usize,
+        filter: Option<&Filter>,
+        after: Option<(Metric, usize)>,
+    ) -> Vec<(Metric, usize)> {
+        self.table().top_k_after_by_metric(
+            self.distance(),
+            &self.prepare(vector),
+            k,
+            self.asc(),
+            filter,
+            after,
+        )
+    }
+
+    fn query_pages<'a>(
+        &'a self,
+        vector: &Vector,
+        page_size: usize,
+        filter: Option<&'a Filter>,
+    ) -> ResultPages<'a> {
+        self.table().pages_by_metric(
+            self.distance(),
+            &self.prepare(vector),
+            page_size,
+            self.asc(),
+            filter,
+        )
+    }
+
+    // Top k results by the aggregated metric to all of the query vectors
+    fn query_multi(
+        &self,
+        vectors: &[Vector],
+        k: usize,
+        aggregation: Aggregation,
+    ) -> Vec<(Metric, usize)> {
+        let prepared: Vec<Vector> = vectors
+            .iter()
+            .map(|vector| self.prepare(vector).into_owned())
+            .collect();
+        self.table()
+            .multi_top_k_by_metric(self.distance(), &prepared, k, self.asc(), aggregation)
+    }
+
+    // Top k results in the filter, planned by the filter's selectivity
+    fn query_planned(
+        &self,
+        vector: &Vector,
+        k: usize,
+        filter: &Filter,
+        planner: &QueryPlanner,
+    ) -> (Vec<(Metric, usize)>, QueryStats) {
+        self.table().planned_top_k_by_metric(
+            self.distance(),
+            &self.prepare(vector),
+            k,
+            self.asc(),
+            filter,
+            planner,
+        )
+    }
+}
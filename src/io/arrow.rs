@@ -0,0 +1,564 @@
+extern crate polars;
+use polars::export::arrow::array::{
+    Array, BinaryArray, BooleanArray, DictionaryArray, DictionaryKey, FixedSizeListArray,
+    ListArray, PrimitiveArray, Utf8Array,
+};
+use polars::export::arrow::chunk::Chunk;
+use polars::export::arrow::datatypes::{
+    DataType, Field, IntegerType, PhysicalType, PrimitiveType, Schema,
+};
+use polars::export::arrow::io::ipc::read::{
+    read_file_metadata, read_stream_metadata, FileReader, StreamReader, StreamState,
+};
+use polars::export::arrow::io::ipc::write::{StreamWriter, WriteOptions};
+use polars::export::arrow::types::NativeType;
+
+use crate::primitives::attributes::Attributes;
+use crate::primitives::filter::Filter;
+use crate::primitives::vector_table::{Metric, VectorTable};
+
+use ndarray::ArrayView2;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+// Reads an Arrow IPC stream, appending the vector column to the table and
+// every other column to the attributes. Returns the number of rows read
+pub fn read_arrow_stream<R: Read>(
+    mut reader: R,
+    vector_col: &str,
+    table: &mut VectorTable,
+    attributes: &mut Attributes,
+) -> usize {
+    let metadata = read_stream_metadata(&mut reader).expect("couldn't read arrow schema");
+    let schema = metadata.schema.clone();
+    let stream = StreamReader::new(reader, metadata, None);
+
+    let mut count = 0;
+    for state in stream {
+        match state.expect("couldn't read record batch") {
+            StreamState::Some(batch) => {
+                count += insert_batch(&batch, &schema, vector_col, table, attributes)
+            }
+            StreamState::Waiting => continue,
+        }
+    }
+    count
+}
+
+// Same as `read_arrow_stream`, for Arrow IPC (Feather v2) files
+pub fn read_arrow_file(
+    file: File,
+    vector_col: &str,
+    table: &mut VectorTable,
+    attributes: &mut Attributes,
+) -> usize {
+    let mut reader = BufReader::new(file);
+    let metadata = read_file_metadata(&mut reader).expect("couldn't read arrow schema");
+    let schema = metadata.schema.clone();
+    let batches = FileReader::new(reader, metadata, None, None);
+
+    let mut count = 0;
+    for batch in batches {
+        let batch = batch.expect("couldn't read record batch");
+        count += insert_batch(&batch, &schema, vector_col, table, attributes);
+    }
+    count
+}
+
+// Appends a record batch, taking vectors from `vector_col` and treating every
+// other column as a categorical attribute. Returns the number of rows appended
+pub(crate) fn insert_batch(
+    batch: &Chunk<Box<dyn Array>>,
+    schema: &Schema,
+    vector_col: &str,
+    table: &mut VectorTable,
+    attributes: &mut Attributes,
+) -> usize {
+    let start_id = table.len() as u32;
+    let mut found_vectors = false;
+
+    for (field, column) in schema.fields.iter().zip(batch.columns()) {
+        if field.name == vector_col {
+            insert_vectors(column.as_ref(), table);
+            found_vectors = true;
+        } else {
+            insert_attributes(&field.name, column.as_ref(), start_id, attributes);
+        }
+    }
+
+    assert!(found_vectors, "Vector column {} not found", vector_col);
+    batch.len()
+}
+
+// Accepts fixed size lists, or variable length lists where every row has
+// the table's dim, of f32 or f64 values
+fn insert_vectors(column: &dyn Array, table: &mut VectorTable) {
+    assert!(column.null_count() == 0, "Vector column contains nulls");
+
+    let dim = table.dim;
+    let any = column.as_any();
+    let (values, start) = if let Some(list) = any.downcast_ref::<FixedSizeListArray>() {
+        let size = match list.data_type().to_logical_type() {
+            DataType::FixedSizeList(_, size) => *size,
+            _ => unreachable!(),
+        };
+        assert!(
+            size == dim,
+            "Vector column dim {} doesn't match table dim",
+            size
+        );
+        (list.values(), 0)
+    } else if let Some(list) = any.downcast_ref::<ListArray<i32>>() {
+        (list.values(), list_start(&list.offsets()[..], dim))
+    } else if let Some(list) = any.downcast_ref::<ListArray<i64>>() {
+        (list.values(), list_start(&list.offsets()[..], dim))
+    } else {
+        panic!(
+            "vector column has unsupported type {:?}",
+            column.data_type()
+        );
+    };
+
+    assert!(values.null_count() == 0, "Vector column contains nulls");
+    let end = start + column.len() * dim;
+
+    // The child values are one contiguous buffer, so f32 batches are
+    // viewed as a matrix in place instead of being converted first
+    let values = values.as_any();
+    if let Some(values) = values.downcast_ref::<PrimitiveArray<f32>>() {
+        table.insert_rows(view_rows(&values.values()[start..end], dim));
+    } else if let Some(values) = values.downcast_ref::<PrimitiveArray<f64>>() {
+        let values: Vec<f32> = values.values()[start..end]
+            .iter()
+            .map(|value| *value as f32)
+            .collect();
+        table.insert_rows(view_rows(&values, dim));
+    } else {
+        panic!("vector column values aren't f32 or f64");
+    }
+}
+
+// Checks that every list has `dim` values and returns where the first begins
+fn list_start<O: Copy + Into<i64>>(offsets: &[O], dim: usize) -> usize {
+    let offending: Vec<usize> = offsets
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| (pair[1].into() - pair[0].into()) as usize != dim)
+        .map(|(row, _)| row)
+        .collect();
+
+    assert!(
+        offending.is_empty(),
+        "Vectors in rows {:?} don't have dim {}",
+        offending,
+        dim
+    );
+    offsets[0].into() as usize
+}
+
+fn view_rows(values: &[f32], dim: usize) -> ArrayView2<'_, f32> {
+    ArrayView2::from_shape((values.len() / dim, dim), values)
+        .expect("couldn't view vector column as a matrix")
+}
+
+// Columns whose type doesn't make sense as a category, like floats or
+// nested lists, are skipped rather than failing the whole batch
+fn insert_attributes(name: &str, column: &dyn Array, start_id: u32, attributes: &mut Attributes) {
+    let values = match attribute_values(column) {
+        Some(values) => values,
+        None => return,
+    };
+
+    for (offset, value) in values.into_iter().enumerate() {
+        if let Some(value) = value {
+            attributes.insert(name, &value, start_id + offset as u32);
+        }
+    }
+}
+
+// Each row of a string, boolean, integer or dictionary-encoded column (like
+// polars categoricals) as a string, or None if the column is another type
+fn attribute_values(column: &dyn Array) -> Option<Vec<Option<String>>> {
+    let values = match column.data_type().to_physical_type() {
+        PhysicalType::Utf8 => strings(downcast::<Utf8Array<i32>>(column).iter()),
+        PhysicalType::LargeUtf8 => strings(downcast::<Utf8Array<i64>>(column).iter()),
+        PhysicalType::Boolean => strings(downcast::<BooleanArray>(column).iter()),
+        PhysicalType::Primitive(primitive) => match primitive {
+            PrimitiveType::Int8 => primitive_values::<i8>(column),
+            PrimitiveType::Int16 => primitive_values::<i16>(column),
+            PrimitiveType::Int32 => primitive_values::<i32>(column),
+            PrimitiveType::Int64 => primitive_values::<i64>(column),
+            PrimitiveType::UInt8 => primitive_values::<u8>(column),
+            PrimitiveType::UInt16 => primitive_values::<u16>(column),
+            PrimitiveType::UInt32 => primitive_values::<u32>(column),
+            PrimitiveType::UInt64 => primitive_values::<u64>(column),
+            _ => return None,
+        },
+        PhysicalType::Dictionary(key) => match key {
+            IntegerType::Int8 => dictionary_values::<i8>(column)?,
+            IntegerType::Int16 => dictionary_values::<i16>(column)?,
+            IntegerType::Int32 => dictionary_values::<i32>(column)?,
+            IntegerType::Int64 => dictionary_values::<i64>(column)?,
+            IntegerType::UInt8 => dictionary_values::<u8>(column)?,
+            IntegerType::UInt16 => dictionary_values::<u16>(column)?,
+            IntegerType::UInt32 => dictionary_values::<u32>(column)?,
+            IntegerType::UInt64 => dictionary_values::<u64>(column)?,
+        },
+        _ => return None,
+    };
+    Some(values)
+}
+
+fn strings<T: ToString, I: Iterator<Item = Option<T>>>(values: I) -> Vec<Option<String>> {
+    values.map(|value| value.map(|v| v.to_string())).collect()
+}
+
+fn primitive_values<T: NativeType + ToString>(column: &dyn Array) -> Vec<Option<String>> {
+    strings(downcast::<PrimitiveArray<T>>(column).iter())
+}
+
+// Decodes the dictionary once, then looks each row's key up in it
+fn dictionary_values<K: DictionaryKey>(column: &dyn Array) -> Option<Vec<Option<String>>> {
+    let dictionary = downcast::<DictionaryArray<K>>(column);
+    let decoded = attribute_values(dictionary.values().as_ref())?;
+
+    Some(
+        dictionary
+            .keys_iter()
+            .map(|key| key.and_then(|key| decoded[key].clone()))
+            .collect(),
+    )
+}
+
+// Writes results as a single record batch with one row per result,
+// holding the query's position, the result's rank, row id and score
+pub fn write_results<W: Write>(writer: W, results: &[Vec<(Metric, usize)>]) {
+    let mut queries = Vec::<u32>::new();
+    let mut ranks = Vec::<u32>::new();
+    let mut ids = Vec::<u64>::new();
+    let mut scores = Vec::<f32>::new();
+
+    for (query, query_results) in results.iter().enumerate() {
+        for (rank, (score, id)) in query_results.iter().enumerate() {
+            queries.push(query as u32);
+            ranks.push(rank as u32);
+            ids.push(*id as u64);
+            scores.push(score.0);
+        }
+    }
+
+    let schema = Schema::from(vec![
+        Field::new("query", DataType::UInt32, false),
+        Field::new("rank", DataType::UInt32, false),
+        Field::new("id", DataType::UInt64, false),
+        Field::new("score", DataType::Float32, false),
+    ]);
+    let batch = Chunk::new(vec![
+        Box::new(PrimitiveArray::from_vec(queries)) as Box<dyn Array>,
+        Box::new(PrimitiveArray::from_vec(ranks)) as Box<dyn Array>,
+        Box::new(PrimitiveArray::from_vec(ids)) as Box<dyn Array>,
+        Box::new(PrimitiveArray::from_vec(scores)) as Box<dyn Array>,
+    ]);
+
+    let mut writer = StreamWriter::new(BufWriter::new(writer), WriteOptions { compression: None });
+    writer
+        .start(&schema, None)
+        .expect("couldn't write arrow schema");
+    writer
+        .write(&batch, None)
+        .expect("couldn't write record batch");
+    writer.finish().expect("couldn't finish arrow stream");
+}
+
+// Writes the attribute dictionaries with one row per distinct value, holding
+// its column, value, code and rows, the last as a portable roaring bitmap.
+// Values are written in code order, so reading them back keeps the codes.
+//
+// This persists the `Attributes` dictionary rather than the polars RevMapping
+// of a `preprocess_df` frame. Rows are coded by `Attributes` as they're loaded,
+// and its codes are the keys of the inverted indexes, so it's the mapping
+// filters need to survive a restart. A RevMapping only maps one frame's
+// categorical codes (or the global string cache's) back to strings, and those
+// codes aren't kept anywhere once the rows are in the table
+pub fn write_attributes<W: Write>(writer: W, attributes: &Attributes) {
+    let mut columns = Vec::<&str>::new();
+    let mut values = Vec::<&str>::new();
+    let mut codes = Vec::<u32>::new();
+    let mut bitmaps = Vec::<Vec<u8>>::new();
+
+    for (column, attribute) in attributes.columns() {
+        for (code, value) in attribute.values().iter().enumerate() {
+            let filter = attribute.filter(value).unwrap();
+            let mut bytes = Vec::with_capacity(filter.serialized_size());
+            filter.serialize_into(&mut bytes);
+
+            columns.push(column);
+            values.push(value);
+            codes.push(code as u32);
+            bitmaps.push(bytes);
+        }
+    }
+
+    let schema = Schema::from(vec![
+        Field::new("column", DataType::Utf8, false),
+        Field::new("value", DataType::Utf8, false),
+        Field::new("code", DataType::UInt32, false),
+        Field::new("ids", DataType::Binary, false),
+    ]);
+    let batch = Chunk::new(vec![
+        Box::new(Utf8Array::<i32>::from_slice(columns)) as Box<dyn Array>,
+        Box::new(Utf8Array::<i32>::from_slice(values)) as Box<dyn Array>,
+        Box::new(PrimitiveArray::from_vec(codes)) as Box<dyn Array>,
+        Box::new(BinaryArray::<i32>::from_slice(bitmaps)) as Box<dyn Array>,
+    ]);
+
+    let mut writer = StreamWriter::new(BufWriter::new(writer), WriteOptions { compression: None });
+    writer
+        .start(&schema, None)
+        .expect("couldn't write arrow schema");
+    writer
+        .write(&batch, None)
+        .expect("couldn't write record batch");
+    writer.finish().expect("couldn't finish arrow stream");
+}
+
+// Reads attribute dictionaries written by `write_attributes`
+pub fn read_attributes<R: Read>(mut reader: R) -> Attributes {
+    let metadata = read_stream_metadata(&mut reader).expect("couldn't read arrow schema");
+    let stream = StreamReader::new(reader, metadata, None);
+
+    let mut attributes = Attributes::new();
+    for state in stream {
+        let batch = match state.expect("couldn't read record batch") {
+            StreamState::Some(batch) => batch,
+            StreamState::Waiting => continue,
+        };
+
+        let columns = batch.columns();
+        let names = downcast::<Utf8Array<i32>>(columns[0].as_ref());
+        let values = downcast::<Utf8Array<i32>>(columns[1].as_ref());
+        let codes = downcast::<PrimitiveArray<u32>>(columns[2].as_ref());
+        let bitmaps = downcast::<BinaryArray<i32>>(columns[3].as_ref());
+
+        for row in 0..batch.len() {
+            let ids: Vec<u32> = Filter::deserialize_from(bitmaps.value(row))
+                .iter()
+                .collect();
+
+            let code = attributes.insert_many(names.value(row), values.value(row), &ids);
+            assert!(
+                code == codes.value(row),
+                "Attribute codes for {} aren't in order",
+                names.value(row)
+            );
+        }
+    }
+    attributes
+}
+
+fn downcast<A: 'static>(column: &dyn Array) -> &A {
+    column
+        .as_any()
+        .downcast_ref::<A>()
+        .expect("column doesn't match its data type")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        insert_batch, insert_vectors, read_arrow_stream, read_attributes, write_attributes,
+        write_results,
+    };
+    use crate::primitives::attributes::Attributes;
+    use crate::primitives::vector_table::VectorTable;
+
+    use polars::export::arrow::array::{
+        Array, BooleanArray, DictionaryArray, FixedSizeListArray, ListArray,
+        MutableDictionaryArray, MutableListArray, MutablePrimitiveArray, MutableUtf8Array,
+        PrimitiveArray, TryExtend, UInt64Array, Utf8Array,
+    };
+    use polars::export::arrow::chunk::Chunk;
+    use polars::export::arrow::datatypes::{DataType, Field, Schema};
+    use polars::export::arrow::io::ipc::read::{read_stream_metadata, StreamReader, StreamState};
+    use polars::export::arrow::io::ipc::write::{StreamWriter, WriteOptions};
+
+    use ordered_float::OrderedFloat;
+
+    #[test]
+    fn read_vectors_and_attributes() {
+        let item = Field::new("item", DataType::Float32, false);
+        let vector_type = DataType::FixedSizeList(Box::new(item), 3);
+        let values = PrimitiveArray::from_vec(vec![1f32, 2., 3., 4., 5., 6.]);
+        let vectors = FixedSizeListArray::new(vector_type.clone(), Box::new(values), None);
+        let fruits = Utf8Array::<i32>::from_slice(["banana", "apple"]);
+
+        let schema = Schema::from(vec![
+            Field::new("fruits", DataType::Utf8, false),
+            Field::new("vectors", vector_type, false),
+        ]);
+        let batch = Chunk::new(vec![
+            Box::new(fruits) as Box<dyn Array>,
+            Box::new(vectors) as Box<dyn Array>,
+        ]);
+
+        let mut bytes = Vec::new();
+        let mut writer = StreamWriter::new(&mut bytes, WriteOptions { compression: None });
+        writer.start(&schema, None).unwrap();
+        writer.write(&batch, None).unwrap();
+        writer.write(&batch, None).unwrap();
+        writer.finish().unwrap();
+
+        let mut table = VectorTable::new(3, true);
+        let mut attributes = Attributes::new();
+        let count = read_arrow_stream(&bytes[..], "vectors", &mut table, &mut attributes);
+
+        assert_eq!(count, 4);
+        assert_eq!(table.len(), 4);
+
+        let fruits = attributes.get("fruits").unwrap();
+        assert_eq!(fruits.values(), ["banana", "apple"]);
+    }
+
+    #[test]
+    fn read_attribute_types() {
+        let item = Field::new("item", DataType::Float32, false);
+        let vector_type = DataType::FixedSizeList(Box::new(item), 1);
+        let values = PrimitiveArray::from_vec(vec![1f32, 2., 3.]);
+        let vectors = FixedSizeListArray::new(vector_type.clone(), Box::new(values), None);
+
+        let mut fruits = MutableDictionaryArray::<u32, MutableUtf8Array<i32>>::new();
+        fruits
+            .try_extend([Some("banana"), None, Some("banana")])
+            .unwrap();
+        let fruits: DictionaryArray<u32> = fruits.into();
+        let ripe = BooleanArray::from_slice([true, false, true]);
+        let sizes = PrimitiveArray::from_vec(vec![3u16, 1, 3]);
+        let weights = PrimitiveArray::from_vec(vec![0.5f64, 0.25, 0.75]);
+
+        let schema = Schema::from(vec![
+            Field::new("fruits", fruits.data_type().clone(), true),
+            Field::new("ripe", DataType::Boolean, false),
+            Field::new("sizes", DataType::UInt16, false),
+            Field::new("weights", DataType::Float64, false),
+            Field::new("vectors", vector_type, false),
+        ]);
+        let batch = Chunk::new(vec![
+            Box::new(fruits) as Box<dyn Array>,
+            Box::new(ripe) as Box<dyn Array>,
+            Box::new(sizes) as Box<dyn Array>,
+            Box::new(weights) as Box<dyn Array>,
+            Box::new(vectors) as Box<dyn Array>,
+        ]);
+
+        let mut table = VectorTable::new(1, false);
+        let mut attributes = Attributes::new();
+        insert_batch(&batch, &schema, "vectors", &mut table, &mut attributes);
+
+        let fruits = attributes.get("fruits").unwrap();
+        assert_eq!(fruits.values(), ["banana"]);
+        assert_eq!(fruits.filter("banana").unwrap().len(), 2);
+        assert_eq!(attributes.get("ripe").unwrap().values(), ["true", "false"]);
+        assert_eq!(attributes.get("sizes").unwrap().values(), ["3", "1"]);
+
+        // Floats aren't categories, so the column is skipped
+        assert!(attributes.get("weights").is_none());
+    }
+
+    #[test]
+    fn read_list_vectors() {
+        let rows = vec![
+            Some(vec![Some(1f64), Some(2.), Some(3.)]),
+            Some(vec![Some(4.), Some(5.), Some(6.)]),
+        ];
+        let mut vectors = MutableListArray::<i64, MutablePrimitiveArray<f64>>::new();
+        vectors.try_extend(rows).unwrap();
+        let vectors: ListArray<i64> = vectors.into();
+
+        let schema = Schema::from(vec![Field::new(
+            "vectors",
+            vectors.data_type().clone(),
+            false,
+        )]);
+        let batch = Chunk::new(vec![Box::new(vectors) as Box<dyn Array>]);
+
+        let mut table = VectorTable::new(3, false);
+        let mut attributes = Attributes::new();
+        let count = insert_batch(&batch, &schema, "vectors", &mut table, &mut attributes);
+
+        assert_eq!(count, 2);
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "rows [1]")]
+    fn reject_ragged_vectors() {
+        let rows = vec![
+            Some(vec![Some(1f32), Some(2.), Some(3.)]),
+            Some(vec![Some(4.), Some(5.)]),
+        ];
+        let mut vectors = MutableListArray::<i32, MutablePrimitiveArray<f32>>::new();
+        vectors.try_extend(rows).unwrap();
+        let vectors: ListArray<i32> = vectors.into();
+
+        let mut table = VectorTable::new(3, false);
+        insert_vectors(&vectors, &mut table);
+    }
+
+    #[test]
+    fn write_and_read_results() {
+        let results = vec![
+            vec![(OrderedFloat(0.5), 3), (OrderedFloat(0.25), 7)],
+            vec![(OrderedFloat(0.75), 1)],
+        ];
+
+        let mut bytes = Vec::new();
+        write_results(&mut bytes, &results);
+
+        let mut reader = &bytes[..];
+        let metadata = read_stream_metadata(&mut reader).unwrap();
+        let field_names: Vec<&str> = metadata
+            .schema
+            .fields
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(field_names, ["query", "rank", "id", "score"]);
+
+        let mut stream = StreamReader::new(reader, metadata, None);
+        let batch = match stream.next().unwrap().unwrap() {
+            StreamState::Some(batch) => batch,
+            StreamState::Waiting => panic!("stream was waiting"),
+        };
+
+        let ids = batch.columns()[2]
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(&ids.values()[..], [3, 7, 1]);
+    }
+
+    #[test]
+    fn write_and_read_attributes() {
+        let mut attributes = Attributes::new();
+        attributes.insert("fruits", "banana", 0);
+        attributes.insert("fruits", "apple", 1);
+        attributes.insert("fruits", "banana", 2);
+        attributes.insert("cars", "audi", 2);
+
+        let mut bytes = Vec::new();
+        write_attributes(&mut bytes, &attributes);
+        let loaded = read_attributes(&bytes[..]);
+
+        let fruits = loaded.get("fruits").unwrap();
+        assert_eq!(fruits.values(), ["banana", "apple"]);
+        assert_eq!(
+            fruits.filter("banana"),
+            attributes.get("fruits").unwrap().filter("banana")
+        );
+        assert_eq!(
+            loaded.get("cars").unwrap().filter("audi"),
+            attributes.get("cars").unwrap().filter("audi")
+        );
+    }
+}
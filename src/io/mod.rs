@@ -1 +1,31 @@
+pub mod arrow;
+pub mod npy;
 pub mod parquet;
+pub mod vecs;
+
+use crate::prelude::*;
+use crate::primitives::vector_table::{VectorTable, CHUNK_SIZE};
+
+// Readers that decode a file one row at a time, converting values to f32.
+// Rows are batched up here so each format only has to decode a single row
+pub trait RowReader: Iterator<Item = Vector> + Sized {
+    // Reads up to `batch_size` rows, returning fewer only at the end of the file
+    fn read_batch(&mut self, batch_size: usize) -> Vec<Vector> {
+        self.by_ref().take(batch_size).collect()
+    }
+
+    // Streams every remaining row into the table, one chunk at a
+    // time, and returns the number of vectors inserted
+    fn insert_into(mut self, table: &mut VectorTable) -> usize {
+        let mut count = 0;
+        loop {
+            let batch = self.read_batch(CHUNK_SIZE);
+            if batch.is_empty() {
+                return count;
+            }
+
+            count += batch.len();
+            table.insert_many(&batch);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+// Reader and writer for 2-D NumPy .npy arrays, one vector per row
+
+use crate::io::RowReader;
+use crate::prelude::*;
+use crate::primitives::vector_table::VectorTable;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use half::f16;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NpyDtype {
+    F16,
+    F32,
+    F64,
+}
+
+impl NpyDtype {
+    fn parse(descr: &str) -> NpyDtype {
+        match descr {
+            "<f2" => NpyDtype::F16,
+            "<f4" => NpyDtype::F32,
+            "<f8" => NpyDtype::F64,
+            _ => panic!("unsupported npy dtype {}", descr),
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        match self {
+            NpyDtype::F16 => 2,
+            NpyDtype::F32 => 4,
+            NpyDtype::F64 => 8,
+        }
+    }
+}
+
+// Parses the header up front, then decodes the row-major data
+pub struct NpyReader<R: Read> {
+    reader: R,
+    dtype: NpyDtype,
+    pub rows: usize,
+    pub dim: usize,
+    pos: usize,
+    buffer: Vec<u8>,
+}
+
+impl NpyReader<BufReader<File>> {
+    pub fn from_file(file: File) -> NpyReader<BufReader<File>> {
+        NpyReader::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> NpyReader<R> {
+    pub fn new(mut reader: R) -> NpyReader<R> {
+        let mut preamble = [0; 8];
+        reader
+            .read_exact(&mut preamble)
+            .expect("couldn't read npy header");
+        assert!(&preamble[..6] == MAGIC, "Not an npy file");
+
+        // Version 1 uses a u16 header length, later versions a u32
+        let header_len = if preamble[6] == 1 {
+            let mut bytes = [0; 2];
+            reader
+                .read_exact(&mut bytes)
+                .expect("couldn't read npy header");
+            u16::from_le_bytes(bytes) as usize
+        } else {
+            let mut bytes = [0; 4];
+            reader
+                .read_exact(&mut bytes)
+                .expect("couldn't read npy header");
+            u32::from_le_bytes(bytes) as usize
+        };
+
+        let mut header = vec![0; header_len];
+        reader
+            .read_exact(&mut header)
+            .expect("couldn't read npy header");
+        let header = String::from_utf8(header).expect("couldn't parse npy header");
+
+        let descr = header_value(&header, "descr");
+        let dtype = NpyDtype::parse(descr.trim_matches(|c| c == '\'' || c == '"'));
+
+        assert!(
+            header_value(&header, "fortran_order") == "False",
+            "Fortran-ordered npy arrays aren't supported"
+        );
+
+        let shape: Vec<usize> = header_value(&header, "shape")
+            .trim_matches(|c| c == '(' || c == ')')
+            .split(',')
+            .map(|dim| dim.trim())
+            .filter(|dim| !dim.is_empty())
+            .map(|dim| dim.parse().expect("couldn't parse npy shape"))
+            .collect();
+        assert!(shape.len() == 2, "Only 2-D npy arrays are supported");
+
+        NpyReader {
+            reader,
+            dtype,
+            rows: shape[0],
+            dim: shape[1],
+            pos: 0,
+            buffer: Vec::<u8>::new(),
+        }
+    }
+}
+
+impl<R: Read> Iterator for NpyReader<R> {
+    type Item = Vector;
+
+    fn next(&mut self) -> Option<Vector> {
+        if self.pos >= self.rows {
+            return None;
+        }
+        self.pos += 1;
+
+        self.buffer.resize(self.dim * self.dtype.bytes(), 0);
+        self.reader
+            .read_exact(&mut self.buffer)
+            .expect("couldn't read vector");
+
+        let vector = match self.dtype {
+            NpyDtype::F16 => self
+                .buffer
+                .chunks_exact(2)
+                .map(|b| f16::from_le_bytes([b[0], b[1]]).to_f32())
+                .collect(),
+            NpyDtype::F32 => self
+                .buffer
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            NpyDtype::F64 => self
+                .buffer
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32)
+                .collect(),
+        };
+
+        Some(vector)
+    }
+}
+
+impl<R: Read> RowReader for NpyReader<R> {}
+
+// Finds the raw text of a value in the header's Python dict literal
+fn header_value<'a>(header: &'a str, key: &str) -> &'a str {
+    let quoted = format!("'{}':", key);
+    let start = header
+        .find(&quoted)
+        .unwrap_or_else(|| panic!("npy header is missing {}", key))
+        + quoted.len();
+    let rest = header[start..].trim_start();
+
+    // Tuples contain commas, so read up to the closing paren
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|end| end + 1)
+    } else {
+        rest.find([',', '}'])
+    };
+
+    rest[..end.expect("couldn't parse npy header")].trim()
+}
+
+pub fn read_npy(file: File, table: &mut VectorTable) -> usize {
+    let reader = NpyReader::from_file(file);
+    assert!(
+        reader.dim == table.dim,
+        "Vector dim doesn't match table dim"
+    );
+    reader.insert_into(table)
+}
+
+// Writes the vectors as a 2-D float32 array
+pub fn write_npy(file: File, vectors: &[Vector]) {
+    let dim = vectors.first().map_or(0, |v| v.len());
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        vectors.len(),
+        dim
+    );
+
+    // Pad so the data starts on a 64 byte boundary, ending in a newline
+    let preamble_len = MAGIC.len() + 2 + 2;
+    let padding = 63 - (preamble_len + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    let mut writer = BufWriter::new(file);
+    writer.write_all(MAGIC).expect("couldn't write npy header");
+    writer
+        .write_all(&[1, 0])
+        .expect("couldn't write npy header");
+    writer
+        .write_all(&(header.len() as u16).to_le_bytes())
+        .expect("couldn't write npy header");
+    writer
+        .write_all(header.as_bytes())
+        .expect("couldn't write npy header");
+
+    for vector in vectors {
+        assert!(vector.len() == dim, "Vector dims don't match");
+        for value in vector.iter() {
+            writer
+                .write_all(&value.to_le_bytes())
+                .expect("couldn't write vector");
+        }
+    }
+
+    writer.flush().expect("couldn't write vectors");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_npy, write_npy, NpyReader};
+    use crate::prelude::*;
+    use crate::primitives::vector::random_vector;
+    use crate::primitives::vector_table::{VectorTable, CHUNK_SIZE};
+
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn npy_write_and_read() {
+        let dim = 8;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE + 5).map(|_| random_vector(dim)).collect();
+
+        let tmpfile = NamedTempFile::new().unwrap();
+        write_npy(tmpfile.reopen().unwrap(), &vectors);
+
+        let mut table = VectorTable::new(dim, true);
+        let count = read_npy(tmpfile.reopen().unwrap(), &mut table);
+
+        assert_eq!(count, vectors.len());
+        assert_eq!(table.len(), vectors.len());
+
+        let reader = NpyReader::from_file(tmpfile.reopen().unwrap());
+        assert_eq!((reader.rows, reader.dim), (vectors.len(), dim));
+        assert_eq!(reader.collect::<Vec<Vector>>(), vectors);
+    }
+
+    #[test]
+    fn read_float64() {
+        // np.save of np.array([[1., 2.], [3., 4.]])
+        let header = "{'descr': '<f8', 'fortran_order': False, 'shape': (2, 2), }";
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        for value in [1f64, 2., 3., 4.] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let vectors: Vec<Vector> = NpyReader::new(&bytes[..]).collect();
+
+        assert_eq!(
+            vectors,
+            [Vector::from(vec![1., 2.]), Vector::from(vec![3., 4.])]
+        );
+    }
+}
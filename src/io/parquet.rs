@@ -1,7 +1,14 @@
 extern crate polars;
 use polars::datatypes::{DataType, RevMapping};
+use polars::export::arrow::io::parquet::read::{infer_schema, read_metadata, FileReader};
 use polars::frame::DataFrame;
 use polars::prelude::{Arc, ParquetReader, ParquetWriter, SerReader};
+use polars::series::Series;
+
+use crate::io::arrow::insert_batch;
+use crate::prelude::*;
+use crate::primitives::attributes::Attributes;
+use crate::primitives::vector_table::{VectorTable, CHUNK_SIZE};
 
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
@@ -13,6 +20,58 @@ pub fn read_parquet(file: File) -> DataFrame {
     reader.finish().expect("couldn't read dataframe")
 }
 
+// How far `stream_parquet` has got, reported after each row group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParquetProgress {
+    pub row_groups_read: usize,
+    pub row_groups: usize,
+    pub rows_read: usize,
+    pub rows: usize,
+}
+
+// Appends a Parquet file to the table and attributes one row group at a
+// time, so memory use is bounded by the largest row group instead of the
+// whole file. Row groups are decoded in batches of at most a chunk's rows
+pub fn stream_parquet<F: FnMut(ParquetProgress)>(
+    file: File,
+    vector_col: &str,
+    table: &mut VectorTable,
+    attributes: &mut Attributes,
+    mut progress: F,
+) -> usize {
+    let mut reader = BufReader::new(file);
+    let metadata = read_metadata(&mut reader).expect("couldn't read parquet metadata");
+    let schema = infer_schema(&metadata).expect("couldn't read parquet schema");
+
+    let mut status = ParquetProgress {
+        row_groups_read: 0,
+        row_groups: metadata.row_groups.len(),
+        rows_read: 0,
+        rows: metadata.num_rows,
+    };
+
+    for row_group in metadata.row_groups {
+        let batches = FileReader::new(
+            &mut reader,
+            vec![row_group],
+            schema.clone(),
+            Some(CHUNK_SIZE),
+            None,
+            None,
+        );
+
+        for batch in batches {
+            let batch = batch.expect("couldn't read row group");
+            status.rows_read += insert_batch(&batch, &schema, vector_col, table, attributes);
+        }
+
+        status.row_groups_read += 1;
+        progress(status);
+    }
+
+    status.rows_read
+}
+
 pub fn write_parquet(file: File, df: &mut DataFrame) {
     let buf_writer = BufWriter::new(file);
     let writer = ParquetWriter::new(buf_writer);
@@ -49,9 +108,80 @@ pub fn preprocess_df(mut df: DataFrame, vector_col: &str) -> DataFrame {
     df
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullPolicy {
+    // Panic on the first null vector or value
+    Reject,
+    // Leave out rows whose vector is null or contains nulls
+    Skip,
+    // Fill null vectors and values with zeros
+    Zero,
+}
+
+// Converts a list column of f32 or f64 vectors into one contiguous matrix,
+// ready to hand to `VectorTable::insert_rows`. Also returns the column row
+// each matrix row came from, since skipped nulls shift the positions
+pub fn vector_matrix(column: &Series, dim: usize, nulls: NullPolicy) -> (Matrix, Vec<usize>) {
+    let column = column
+        .cast(&DataType::List(Box::new(DataType::Float32)))
+        .expect("couldn't cast vector column to f32");
+    let lists = column.list().expect("vector column isn't a list");
+
+    let offending: Vec<usize> = lists
+        .into_iter()
+        .enumerate()
+        .filter(|(_, vector)| matches!(vector, Some(vector) if vector.len() != dim))
+        .map(|(row, _)| row)
+        .collect();
+    assert!(
+        offending.is_empty(),
+        "Vectors in rows {:?} don't have dim {}",
+        offending,
+        dim
+    );
+
+    let mut values = Vec::<f32>::with_capacity(lists.len() * dim);
+    let mut rows = Vec::<usize>::with_capacity(lists.len());
+
+    for (row, vector) in lists.into_iter().enumerate() {
+        let vector = match vector {
+            Some(vector) => vector,
+            None => match nulls {
+                NullPolicy::Reject => panic!("Vector in row {} is null", row),
+                NullPolicy::Skip => continue,
+                NullPolicy::Zero => {
+                    values.resize(values.len() + dim, 0.);
+                    rows.push(row);
+                    continue;
+                }
+            },
+        };
+
+        let vector = vector.f32().expect("couldn't read vector as f32");
+        if vector.null_count() > 0 {
+            match nulls {
+                NullPolicy::Reject => panic!("Vector in row {} contains nulls", row),
+                NullPolicy::Skip => continue,
+                NullPolicy::Zero => {}
+            }
+        }
+
+        values.extend(vector.into_iter().map(|value| value.unwrap_or(0.)));
+        rows.push(row);
+    }
+
+    let matrix = Matrix::from_shape_vec((rows.len(), dim), values)
+        .expect("couldn't build matrix from vector column");
+    (matrix, rows)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{preprocess_df, read_parquet, write_parquet};
+    use super::{
+        preprocess_df, read_parquet, stream_parquet, vector_matrix, write_parquet, NullPolicy,
+    };
+    use crate::primitives::attributes::Attributes;
+    use crate::primitives::vector_table::VectorTable;
     use polars::prelude::*;
 
     #[test]
@@ -115,4 +245,98 @@ mod tests {
 
         assert_eq!(df, read_df)
     }
+
+    #[test]
+    fn stream_parquet_into_table() {
+        use tempfile::NamedTempFile;
+
+        let s0 = Series::new("fruits", &["banana", "banana", "apple", "apple", "banana"]);
+        let vectors = [
+            [1., 2., 3.],
+            [4., 5., 6.],
+            [7., 8., 9.],
+            [1., 4., 7.],
+            [2., 5., 8.],
+        ]
+        .map(|l| Series::new("", l));
+        let vector_series = Series::new("vectors", vectors);
+
+        let mut df = DataFrame::new(vec![s0, vector_series]).unwrap();
+
+        let tmpfile = NamedTempFile::new().unwrap();
+        let tmpfile_read = tmpfile.reopen().unwrap();
+        write_parquet(tmpfile.into_file(), &mut df);
+
+        let mut table = VectorTable::new(3, true);
+        let mut attributes = Attributes::new();
+        let mut reports = Vec::new();
+        let count = stream_parquet(
+            tmpfile_read,
+            "vectors",
+            &mut table,
+            &mut attributes,
+            |progress| reports.push(progress),
+        );
+
+        assert_eq!(count, 5);
+        assert_eq!(table.len(), 5);
+
+        let last = reports.last().unwrap();
+        assert_eq!(last.row_groups_read, last.row_groups);
+        assert_eq!(last.rows_read, 5);
+
+        let fruits = attributes.get("fruits").unwrap();
+        assert_eq!(fruits.values(), ["banana", "apple"]);
+    }
+
+    #[test]
+    fn stream_categorical_parquet() {
+        use tempfile::NamedTempFile;
+
+        let s0 = Series::new("fruits", &["banana", "banana", "apple", "apple", "banana"]);
+        let vectors =
+            [[1., 2.], [3., 4.], [5., 6.], [7., 8.], [9., 10.]].map(|l| Series::new("", l));
+        let vector_series = Series::new("vectors", vectors);
+
+        // Categorical columns are written to Parquet dictionary-encoded
+        let df = DataFrame::new(vec![s0, vector_series]).unwrap();
+        let mut df = preprocess_df(df, "vectors");
+
+        let tmpfile = NamedTempFile::new().unwrap();
+        let tmpfile_read = tmpfile.reopen().unwrap();
+        write_parquet(tmpfile.into_file(), &mut df);
+
+        let mut table = VectorTable::new(2, true);
+        let mut attributes = Attributes::new();
+        let count = stream_parquet(tmpfile_read, "vectors", &mut table, &mut attributes, |_| {});
+
+        assert_eq!(count, 5);
+        let fruits = attributes.get("fruits").unwrap();
+        assert_eq!(fruits.filter("banana").unwrap().len(), 3);
+        assert_eq!(fruits.filter("apple").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn vector_column_to_matrix() {
+        let vectors = [Some([1., 2.]), None, Some([3., 4.])].map(|l| l.map(|l| Series::new("", l)));
+        let vector_series = Series::new("vectors", vectors);
+
+        let (matrix, rows) = vector_matrix(&vector_series, 2, NullPolicy::Skip);
+        assert_eq!(matrix, ndarray::arr2(&[[1f32, 2.], [3., 4.]]));
+        assert_eq!(rows, [0, 2]);
+
+        let (matrix, rows) = vector_matrix(&vector_series, 2, NullPolicy::Zero);
+        assert_eq!(matrix.row(1), ndarray::arr1(&[0f32, 0.]));
+        assert_eq!(rows, [0, 1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "rows [1, 3]")]
+    fn reject_ragged_vector_column() {
+        let vectors =
+            [vec![1., 2.], vec![3.], vec![4., 5.], vec![6., 7., 8.]].map(|l| Series::new("", l));
+        let vector_series = Series::new("vectors", vectors);
+
+        vector_matrix(&vector_series, 2, NullPolicy::Reject);
+    }
 }
@@ -0,0 +1,187 @@
+// Readers and writers for the .fvecs, .ivecs and .bvecs formats used by
+// standard ANN benchmark datasets (SIFT1M, GIST1M, Deep1B). Each row is a
+// little-endian i32 dimension followed by that many f32, i32 or u8 values
+
+use crate::io::RowReader;
+use crate::prelude::*;
+use crate::primitives::vector_table::VectorTable;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VecsFormat {
+    Fvecs,
+    Ivecs,
+    Bvecs,
+}
+
+impl VecsFormat {
+    fn value_bytes(&self) -> usize {
+        match self {
+            VecsFormat::Fvecs | VecsFormat::Ivecs => 4,
+            VecsFormat::Bvecs => 1,
+        }
+    }
+}
+
+// Checks every row's dim prefix against the first row's
+pub struct VecsReader<R: Read> {
+    reader: R,
+    format: VecsFormat,
+    dim: Option<usize>,
+    buffer: Vec<u8>,
+}
+
+impl VecsReader<BufReader<File>> {
+    pub fn from_file(file: File, format: VecsFormat) -> VecsReader<BufReader<File>> {
+        VecsReader::new(BufReader::new(file), format)
+    }
+}
+
+impl<R: Read> VecsReader<R> {
+    pub fn new(reader: R, format: VecsFormat) -> VecsReader<R> {
+        VecsReader {
+            reader,
+            format,
+            dim: None,
+            buffer: Vec::<u8>::new(),
+        }
+    }
+
+    fn read_dim(&mut self) -> Option<usize> {
+        let mut bytes = [0; 4];
+        match self.reader.read_exact(&mut bytes) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return None,
+            Err(err) => panic!("couldn't read vector dim: {}", err),
+        }
+
+        let dim = i32::from_le_bytes(bytes);
+        assert!(dim > 0, "Invalid vector dim {}", dim);
+
+        let dim = dim as usize;
+        if let Some(expected) = self.dim {
+            assert!(dim == expected, "Vector dims in file don't match");
+        }
+        self.dim = Some(dim);
+
+        Some(dim)
+    }
+}
+
+impl<R: Read> Iterator for VecsReader<R> {
+    type Item = Vector;
+
+    fn next(&mut self) -> Option<Vector> {
+        let dim = self.read_dim()?;
+
+        self.buffer.resize(dim * self.format.value_bytes(), 0);
+        self.reader
+            .read_exact(&mut self.buffer)
+            .expect("couldn't read vector");
+
+        let vector = match self.format {
+            VecsFormat::Fvecs => self
+                .buffer
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            VecsFormat::Ivecs => self
+                .buffer
+                .chunks_exact(4)
+                .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32)
+                .collect(),
+            VecsFormat::Bvecs => self.buffer.iter().map(|b| *b as f32).collect(),
+        };
+
+        Some(vector)
+    }
+}
+
+impl<R: Read> RowReader for VecsReader<R> {}
+
+pub fn read_vecs(file: File, format: VecsFormat, table: &mut VectorTable) -> usize {
+    VecsReader::from_file(file, format).insert_into(table)
+}
+
+// Values are rounded and saturated when writing integer formats
+pub fn write_vecs(file: File, format: VecsFormat, vectors: &[Vector]) {
+    let mut writer = BufWriter::new(file);
+
+    for vector in vectors {
+        writer
+            .write_all(&(vector.len() as i32).to_le_bytes())
+            .expect("couldn't write vector dim");
+
+        for value in vector.iter() {
+            let result = match format {
+                VecsFormat::Fvecs => writer.write_all(&value.to_le_bytes()),
+                VecsFormat::Ivecs => writer.write_all(&(value.round() as i32).to_le_bytes()),
+                VecsFormat::Bvecs => writer.write_all(&[value.round() as u8]),
+            };
+            result.expect("couldn't write vector");
+        }
+    }
+
+    writer.flush().expect("couldn't write vectors");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_vecs, write_vecs, VecsFormat, VecsReader};
+    use crate::io::RowReader;
+    use crate::prelude::*;
+    use crate::primitives::vector::random_vector;
+    use crate::primitives::vector_table::{VectorTable, CHUNK_SIZE};
+
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn fvecs_write_and_read() {
+        let dim = 8;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE + 5).map(|_| random_vector(dim)).collect();
+
+        let tmpfile = NamedTempFile::new().unwrap();
+        let tmpfile_read = tmpfile.reopen().unwrap();
+        write_vecs(tmpfile.reopen().unwrap(), VecsFormat::Fvecs, &vectors);
+
+        let mut table = VectorTable::new(dim, true);
+        let count = read_vecs(tmpfile_read, VecsFormat::Fvecs, &mut table);
+
+        assert_eq!(count, vectors.len());
+        assert_eq!(table.len(), vectors.len());
+
+        let read: Vec<Vector> =
+            VecsReader::from_file(tmpfile.reopen().unwrap(), VecsFormat::Fvecs).collect();
+        assert_eq!(read, vectors);
+    }
+
+    #[test]
+    fn integer_formats_write_and_read() {
+        let vectors = vec![
+            Vector::from(vec![0., 1., 255.]),
+            Vector::from(vec![17., 42., 3.]),
+        ];
+
+        for format in [VecsFormat::Ivecs, VecsFormat::Bvecs] {
+            let tmpfile = NamedTempFile::new().unwrap();
+            write_vecs(tmpfile.reopen().unwrap(), format, &vectors);
+
+            let mut reader = VecsReader::from_file(tmpfile.reopen().unwrap(), format);
+            assert_eq!(reader.read_batch(10), vectors);
+            assert_eq!(reader.next(), None);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Vector dims in file don't match")]
+    fn mismatched_dims() {
+        let vectors = vec![random_vector(3), random_vector(4)];
+
+        let tmpfile = NamedTempFile::new().unwrap();
+        write_vecs(tmpfile.reopen().unwrap(), VecsFormat::Fvecs, &vectors);
+
+        VecsReader::from_file(tmpfile.reopen().unwrap(), VecsFormat::Fvecs).for_each(drop);
+    }
+}
@@ -3,6 +3,7 @@ pub mod indexes;
 pub mod io;
 pub mod prelude;
 pub mod primitives;
+pub mod python;
 
 #[cfg(test)]
 mod tests {}
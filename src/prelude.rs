@@ -5,3 +5,7 @@ use ndarray::{Array1, Array2};
 
 pub type Vector = Array1<f32>;
 pub type Matrix = Array2<f32>;
+
+// Bit-packed binary codes, 64 dimensions per word
+pub type BinaryVector = Array1<u64>;
+pub type BinaryMatrix = Array2<u64>;
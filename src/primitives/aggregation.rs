@@ -0,0 +1,49 @@
+// How the scores of several query vectors against
+// the same row are combined into a single score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    Sum,
+    Mean,
+    Max,
+    Min,
+}
+
+impl Aggregation {
+    pub fn combine(&self, acc: f32, score: f32) -> f32 {
+        match self {
+            Aggregation::Sum | Aggregation::Mean => acc + score,
+            Aggregation::Max => acc.max(score),
+            Aggregation::Min => acc.min(score),
+        }
+    }
+
+    pub fn finish(&self, acc: f32, count: usize) -> f32 {
+        match self {
+            Aggregation::Mean => acc / count as f32,
+            _ => acc,
+        }
+    }
+
+    // Aggregates the scores in one go
+    pub fn aggregate(&self, scores: &[f32]) -> f32 {
+        let acc = scores[1..]
+            .iter()
+            .fold(scores[0], |acc, score| self.combine(acc, *score));
+        self.finish(acc, scores.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aggregation;
+
+    #[test]
+    fn aggregate() {
+        let scores = [1., 4., 2., 5.];
+
+        assert_eq!(Aggregation::Sum.aggregate(&scores), 12.);
+        assert_eq!(Aggregation::Mean.aggregate(&scores), 3.);
+        assert_eq!(Aggregation::Max.aggregate(&scores), 5.);
+        assert_eq!(Aggregation::Min.aggregate(&scores), 1.);
+    }
+}
@@ -0,0 +1,234 @@
+use super::filter::Filter;
+use super::inverted_index::{FilterOp, InvertedIndex};
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+// A dictionary-encoded categorical column. Each distinct value gets a
+// u32 code, and the inverted index maps codes to the rows that have them
+#[derive(Debug)]
+pub struct Attribute {
+    values: Vec<String>,
+    codes: HashMap<String, u32>,
+    index: InvertedIndex,
+    // Every (row, code) pair sorted by row, for grouping scanned rows.
+    // Built on first use and dropped whenever a row is inserted
+    memberships: OnceLock<Vec<(u32, u32)>>,
+}
+
+impl Default for Attribute {
+    fn default() -> Attribute {
+        Attribute::new()
+    }
+}
+
+impl Attribute {
+    pub fn new() -> Attribute {
+        Attribute {
+            values: Vec::<String>::new(),
+            codes: HashMap::new(),
+            index: InvertedIndex::new(),
+            memberships: OnceLock::new(),
+        }
+    }
+
+    pub fn index(&self) -> &InvertedIndex {
+        &self.index
+    }
+
+    pub fn memberships(&self) -> &[(u32, u32)] {
+        self.memberships.get_or_init(|| {
+            let mut memberships: Vec<(u32, u32)> = self
+                .index
+                .iter()
+                .flat_map(|(code, postings)| postings.ids().map(move |id| (id, *code)))
+                .collect();
+            memberships.sort_unstable();
+            memberships
+        })
+    }
+
+    pub fn code(&self, value: &str) -> Option<u32> {
+        self.codes.get(value).copied()
+    }
+
+    pub fn value(&self, code: u32) -> Option<&str> {
+        self.values.get(code as usize).map(|value| value.as_str())
+    }
+
+    // Distinct values, ordered by code
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+
+    // Rows that have the value, for filtering queries by it
+    pub fn filter(&self, value: &str) -> Option<Filter> {
+        let code = self.code(value)?;
+        Some(self.index.to_filter(&[code], FilterOp::Or))
+    }
+
+    // Number of rows with each value, counting only rows in the filter
+    // when there is one. Values without any counted rows are left out
+    pub fn facet_counts(&self, filter: Option<&Filter>) -> BTreeMap<String, u64> {
+        let mut counts = BTreeMap::new();
+        for (code, value) in self.values.iter().enumerate() {
+            let postings = match self.index.get(code as u32) {
+                Some(postings) => postings,
+                None => continue,
+            };
+            let count = match filter {
+                Some(filter) => postings.bitmap().intersection_len(filter.bitmap()),
+                None => postings.len(),
+            };
+            if count > 0 {
+                counts.insert(value.clone(), count);
+            }
+        }
+        counts
+    }
+
+    pub fn insert(&mut self, value: &str, id: u32) -> u32 {
+        let code = self.encode(value);
+        self.index.insert(code, id);
+        self.memberships.take();
+        code
+    }
+
+    pub fn insert_many(&mut self, value: &str, ids: &[u32]) -> u32 {
+        let code = self.encode(value);
+        self.index.insert_many(code, ids);
+        self.memberships.take();
+        code
+    }
+
+    fn encode(&mut self, value: &str) -> u32 {
+        match self.codes.get(value) {
+            Some(code) => *code,
+            None => {
+                let code = self.values.len() as u32;
+                self.values.push(value.to_string());
+                self.codes.insert(value.to_string(), code);
+                code
+            }
+        }
+    }
+}
+
+// Categorical attributes of the indexed rows, by column name
+#[derive(Debug)]
+pub struct Attributes {
+    columns: BTreeMap<String, Attribute>,
+}
+
+impl Default for Attributes {
+    fn default() -> Attributes {
+        Attributes::new()
+    }
+}
+
+impl Attributes {
+    pub fn new() -> Attributes {
+        Attributes {
+            columns: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, column: &str) -> Option<&Attribute> {
+        self.columns.get(column)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = (&String, &Attribute)> {
+        self.columns.iter()
+    }
+
+    pub fn insert(&mut self, column: &str, value: &str, id: u32) -> u32 {
+        self.column_mut(column).insert(value, id)
+    }
+
+    pub fn insert_many(&mut self, column: &str, value: &str, ids: &[u32]) -> u32 {
+        self.column_mut(column).insert_many(value, ids)
+    }
+
+    fn column_mut(&mut self, column: &str) -> &mut Attribute {
+        if !self.columns.contains_key(column) {
+            self.columns.insert(column.to_string(), Attribute::new());
+        }
+        self.columns.get_mut(column).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Attribute, Attributes};
+    use crate::primitives::filter::Filter;
+
+    #[test]
+    fn insert() {
+        let mut attributes = Attributes::new();
+
+        attributes.insert("fruits", "banana", 0);
+        attributes.insert("fruits", "apple", 1);
+        attributes.insert("fruits", "banana", 2);
+        attributes.insert("cars", "audi", 2);
+
+        let fruits = attributes.get("fruits").unwrap();
+
+        assert_eq!(fruits.code("banana"), Some(0));
+        assert_eq!(fruits.code("apple"), Some(1));
+        assert_eq!(fruits.value(1), Some("apple"));
+        assert_eq!(fruits.code("cherry"), None);
+
+        let columns: Vec<&String> = attributes.columns().map(|(name, _)| name).collect();
+        assert_eq!(columns, ["cars", "fruits"]);
+    }
+
+    #[test]
+    fn filter() {
+        let mut attributes = Attributes::new();
+
+        attributes.insert("fruits", "banana", 0);
+        attributes.insert("fruits", "apple", 1);
+        attributes.insert_many("fruits", "banana", &[4, 7]);
+
+        let bananas = attributes.get("fruits").unwrap().filter("banana").unwrap();
+
+        let mut expected = Filter::new();
+        expected.insert_many(&[0, 4, 7]);
+
+        assert_eq!(bananas, expected);
+        assert!(attributes.get("fruits").unwrap().filter("cherry").is_none());
+    }
+
+    #[test]
+    fn memberships() {
+        let mut fruits = Attribute::new();
+        fruits.insert_many("banana", &[4, 0]);
+        fruits.insert("apple", 2);
+        assert_eq!(fruits.memberships(), [(0, 0), (2, 1), (4, 0)]);
+
+        // Inserting drops the cached memberships
+        fruits.insert("apple", 1);
+        assert_eq!(fruits.memberships(), [(0, 0), (1, 1), (2, 1), (4, 0)]);
+    }
+
+    #[test]
+    fn facet_counts() {
+        let mut attributes = Attributes::new();
+        attributes.insert_many("fruits", "banana", &[0, 2, 4]);
+        attributes.insert_many("fruits", "apple", &[1, 3]);
+        attributes.insert_many("fruits", "cherry", &[5]);
+
+        let fruits = attributes.get("fruits").unwrap();
+        let counts = fruits.facet_counts(None);
+        assert_eq!(counts["banana"], 3);
+        assert_eq!(counts["cherry"], 1);
+
+        let mut filter = Filter::new();
+        filter.insert_many(&[0, 1, 2]);
+        let counts = fruits.facet_counts(Some(&filter));
+
+        assert_eq!(counts["banana"], 2);
+        assert_eq!(counts["apple"], 1);
+        assert!(!counts.contains_key("cherry"));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::distances::BinaryDistance;
+use crate::prelude::*;
+use crate::primitives::filter::Filter;
+use crate::primitives::mmr::select_mmr;
+use crate::primitives::vector::binary_words;
+use crate::primitives::vector_table::{push_bounded, Metric};
+
+use ndarray::Axis;
+
+use min_max_heap::MinMaxHeap;
+use ordered_float::OrderedFloat;
+
+const CHUNK_SIZE: usize = 4096;
+
+// Stores bit-packed codes in fixed-size chunks, filling
+// a partial chunk in place until it reaches the chunk size
+#[derive(Debug, PartialEq)]
+pub struct BinaryTable {
+    pub dim: usize,
+    pub words: usize,
+    tail: BinaryMatrix,
+    chunks: Vec<BinaryMatrix>,
+}
+
+impl BinaryTable {
+    pub fn new(dim: usize) -> BinaryTable {
+        let words = binary_words(dim);
+        BinaryTable {
+            dim,
+            words,
+            tail: BinaryMatrix::zeros((0, words)),
+            chunks: Vec::<BinaryMatrix>::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len() * CHUNK_SIZE + self.tail.nrows()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn insert(&mut self, code: &BinaryVector) {
+        self.check_dims(code);
+        self.tail
+            .push_row(code.view())
+            .expect("couldn't append code");
+
+        if self.tail.nrows() >= CHUNK_SIZE {
+            let full = std::mem::replace(&mut self.tail, BinaryMatrix::zeros((0, self.words)));
+            self.chunks.push(full);
+        }
+    }
+
+    pub fn insert_many(&mut self, codes: &[BinaryVector]) {
+        for code in codes {
+            self.check_dims(code);
+        }
+
+        for code in codes {
+            self.insert(code);
+        }
+    }
+
+    pub fn get(&self, pos: usize) -> Option<BinaryVector> {
+        if pos >= self.len() {
+            return None;
+        }
+
+        let chunk = self.chunks.get(pos / CHUNK_SIZE).unwrap_or(&self.tail);
+        Some(chunk.index_axis(Axis(0), pos % CHUNK_SIZE).to_owned())
+    }
+
+    fn check_dims(&self, code: &BinaryVector) {
+        assert!(
+            code.len() == self.words,
+            "Code length doesn't match table dim"
+        );
+
+        // Bits past the last dimension would count towards every distance
+        let padding = self.dim % 64;
+        assert!(
+            padding == 0 || code[self.words - 1] >> padding == 0,
+            "Code has bits set past the table dim"
+        );
+    }
+
+    pub fn top_k_by_metric(
+        &self,
+        distance: &dyn BinaryDistance,
+        code: &BinaryVector,
+        k: usize,
+        filter: Option<&Filter>,
+    ) -> Vec<(Metric, usize)> {
+        let mut heap: MinMaxHeap<(Metric, usize)> = MinMaxHeap::with_capacity(k);
+
+        self.scan(distance, code, filter, |metric, pos| {
+            push_bounded(&mut heap, k, true, (metric, pos))
+        });
+
+        heap.into_vec_asc()
+    }
+
+    // Every code within `radius` of the query, nearest first, keeping
+    // only the nearest `limit` when a limit is given
+    pub fn range_by_metric(
+        &self,
+        distance: &dyn BinaryDistance,
+        code: &BinaryVector,
+        radius: f32,
+        filter: Option<&Filter>,
+        limit: Option<usize>,
+    ) -> Vec<(Metric, usize)> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut heap = MinMaxHeap::new();
+
+        self.scan(distance, code, filter, |metric, pos| {
+            if metric.0 <= radius {
+                push_bounded(&mut heap, limit, true, (metric, pos));
+            }
+        });
+
+        heap.into_vec_asc()
+    }
+
+    // Fetches the nearest `fetch_k` codes, then picks k of them by
+    // maximal marginal relevance, using the same distance between codes
+    pub fn mmr_top_k_by_metric(
+        &self,
+        distance: &dyn BinaryDistance,
+        code: &BinaryVector,
+        k: usize,
+        fetch_k: usize,
+        lambda: f32,
+    ) -> Vec<(Metric, usize)> {
+        let candidates = self.top_k_by_metric(distance, code, fetch_k.max(k), None);
+
+        let mut pool = BinaryMatrix::zeros((candidates.len(), self.words));
+        for (mut row, (_, pos)) in pool.rows_mut().into_iter().zip(&candidates) {
+            row.assign(&self.get(*pos).unwrap());
+        }
+
+        select_mmr(&candidates, k, lambda, true, |i| {
+            distance
+                .matrix_dist(&pool.row(i).to_owned(), &pool)
+                .to_vec()
+        })
+    }
+
+    // Passes the distance to every code allowed by the filter to `visit`
+    fn scan<F: FnMut(Metric, usize)>(
+        &self,
+        distance: &dyn BinaryDistance,
+        code: &BinaryVector,
+        filter: Option<&Filter>,
+        mut visit: F,
+    ) {
+        self.check_dims(code);
+
+        for (chunk_index, chunk) in self.chunks.iter().chain([&self.tail]).enumerate() {
+            if chunk.nrows() == 0 {
+                continue;
+            }
+
+            let chunk_pos = chunk_index * CHUNK_SIZE;
+            let results = distance.matrix_dist(code, chunk);
+
+            for (offset, result) in results.iter().enumerate() {
+                let pos = chunk_pos + offset;
+                if filter.is_none_or(|filter| filter.contains(pos as u32)) {
+                    visit(OrderedFloat(*result), pos);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BinaryTable, CHUNK_SIZE};
+    use crate::distances::binary::{Hamming, Jaccard};
+    use crate::prelude::*;
+    use crate::primitives::filter::Filter;
+    use crate::primitives::vector::random_binary_vector;
+
+    #[test]
+    fn insert_many() {
+        let dim = 100;
+        let codes: Vec<BinaryVector> = (0..CHUNK_SIZE + 10)
+            .map(|_| random_binary_vector(dim))
+            .collect();
+
+        let mut table = BinaryTable::new(dim);
+        table.insert_many(&codes);
+
+        assert_eq!(table.len(), CHUNK_SIZE + 10);
+        assert_eq!(table.get(5), Some(codes[5].clone()));
+        assert_eq!(
+            table.get(CHUNK_SIZE + 3),
+            Some(codes[CHUNK_SIZE + 3].clone())
+        );
+        assert_eq!(table.get(CHUNK_SIZE + 10), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Code has bits set past the table dim")]
+    fn insert_padding_bits() {
+        let mut table = BinaryTable::new(8);
+        table.insert(&BinaryVector::from(vec![0b1_0000_0001]));
+    }
+
+    #[test]
+    fn hamming_top_k() {
+        let mut table = BinaryTable::new(8);
+        table.insert_many(&[
+            BinaryVector::from(vec![0b1111]),
+            BinaryVector::from(vec![0b0001]),
+            BinaryVector::from(vec![0b0011]),
+        ]);
+
+        let query = BinaryVector::from(vec![0b0001]);
+        let results = table.top_k_by_metric(&Hamming {}, &query, 2, None);
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+
+        assert_eq!(positions, [1, 2]);
+        assert_eq!(results[1].0 .0, 1.)
+    }
+
+    #[test]
+    fn hamming_range() {
+        let mut table = BinaryTable::new(8);
+        table.insert_many(&[
+            BinaryVector::from(vec![0b1111]),
+            BinaryVector::from(vec![0b0001]),
+            BinaryVector::from(vec![0b0011]),
+        ]);
+
+        let query = BinaryVector::from(vec![0b0001]);
+        let results = table.range_by_metric(&Hamming {}, &query, 1., None, None);
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+
+        assert_eq!(positions, [1, 2]);
+    }
+
+    #[test]
+    fn jaccard_top_k_filtered() {
+        let mut table = BinaryTable::new(8);
+        table.insert_many(&[
+            BinaryVector::from(vec![0b0110]),
+            BinaryVector::from(vec![0b0111]),
+            BinaryVector::from(vec![0b1000]),
+        ]);
+
+        let mut filter = Filter::new();
+        filter.insert_many(&[0, 2]);
+
+        let query = BinaryVector::from(vec![0b0111]);
+        let results = table.top_k_by_metric(&Jaccard {}, &query, 3, Some(&filter));
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+
+        assert_eq!(positions, [0, 2]);
+        assert!((results[0].0 .0 - 1. / 3.).abs() < 1e-6);
+        assert_eq!(results[1].0 .0, 1.)
+    }
+}
@@ -0,0 +1,86 @@
+use crate::primitives::vector_table::Metric;
+
+use ndarray_rand::rand::rngs::StdRng;
+use ndarray_rand::rand::Rng;
+use ordered_float::OrderedFloat;
+
+use std::cmp::Reverse;
+
+// Ways of trading some closeness for variety in query results
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exploration {
+    // Samples k of the best `pool` rows without replacement, with a softmax
+    // over their scores. Higher temperatures flatten the distribution
+    Softmax { pool: usize, temperature: f32 },
+    // Each result is a random row with probability epsilon,
+    // and otherwise the best row not yet returned
+    EpsilonGreedy { epsilon: f32 },
+}
+
+// Samples k candidates without replacement, in the order they're drawn, with
+// probabilities from a softmax over their metrics. Perturbing each logit with
+// Gumbel noise and keeping the top k is equivalent to drawing one at a time.
+// When `asc` is true the metric is a distance, so it's negated first
+pub fn sample_softmax(
+    candidates: &[(Metric, usize)],
+    k: usize,
+    temperature: f32,
+    asc: bool,
+    rng: &mut StdRng,
+) -> Vec<(Metric, usize)> {
+    assert!(temperature > 0., "Softmax temperature must be positive");
+    let sign = if asc { -1. } else { 1. };
+
+    let mut keyed: Vec<(OrderedFloat<f32>, (Metric, usize))> = candidates
+        .iter()
+        .map(|candidate| {
+            let uniform: f32 = rng.gen_range(f32::EPSILON..1.);
+            let gumbel = -(-uniform.ln()).ln();
+            let logit = sign * candidate.0 .0 / temperature;
+            (OrderedFloat(logit + gumbel), *candidate)
+        })
+        .collect();
+
+    keyed.sort_by_key(|key| Reverse(key.0));
+    keyed
+        .iter()
+        .take(k)
+        .map(|(_, candidate)| *candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sample_softmax;
+
+    use ndarray_rand::rand::rngs::StdRng;
+    use ndarray_rand::rand::SeedableRng;
+    use ordered_float::OrderedFloat;
+
+    #[test]
+    fn softmax_temperature() {
+        let candidates: Vec<_> = (0..10).map(|pos| (OrderedFloat(pos as f32), pos)).collect();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        // Nearly greedy when cold
+        let results = sample_softmax(&candidates, 3, 0.001, true, &mut rng);
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        assert_eq!(positions, [0, 1, 2]);
+
+        // Reproducible from the seed
+        let sample = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            sample_softmax(&candidates, 5, 100., true, &mut rng)
+        };
+        assert_eq!(sample(3), sample(3));
+        assert_ne!(sample(3), sample(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "temperature must be positive")]
+    fn softmax_zero_temperature() {
+        let candidates = [(OrderedFloat(1.), 0), (OrderedFloat(2.), 1)];
+        let mut rng = StdRng::seed_from_u64(7);
+        sample_softmax(&candidates, 1, 0., true, &mut rng);
+    }
+}
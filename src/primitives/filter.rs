@@ -1,6 +1,9 @@
 extern crate roaring;
 use roaring::RoaringBitmap;
 
+use std::io::{Read, Write};
+use std::ops::Range;
+
 #[derive(Debug, PartialEq)]
 pub struct Filter {
     bitmap: RoaringBitmap,
@@ -17,6 +20,12 @@ impl Filter {
         Filter { bitmap: bitmap }
     }
 
+    pub fn from_range(ids: Range<u32>) -> Filter {
+        let mut bitmap = RoaringBitmap::new();
+        bitmap.insert_range(ids);
+        Filter::from_bitmap(bitmap)
+    }
+
     pub fn insert(&mut self, id: u32) {
         self.bitmap.insert(id);
     }
@@ -27,12 +36,113 @@ impl Filter {
         }
     }
 
+    pub fn contains(&self, id: u32) -> bool {
+        self.bitmap.contains(id)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.bitmap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bitmap.is_empty()
+    }
+
+    // The nth smallest id in the filter
+    pub fn select(&self, n: u32) -> Option<u32> {
+        self.bitmap.select(n)
+    }
+
+    // Size of the intersection, without building it
+    pub fn intersection_len(&self, other: &Filter) -> u64 {
+        self.bitmap.intersection_len(&other.bitmap)
+    }
+
+    // Ids in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.bitmap.iter()
+    }
+
+    pub(crate) fn bitmap(&self) -> &RoaringBitmap {
+        &self.bitmap
+    }
+
+    // Number of ids below `universe` that are in the filter, where
+    // `universe` is usually the number of rows in the table
+    pub fn len_below(&self, universe: usize) -> u64 {
+        match universe.checked_sub(1) {
+            None => 0,
+            Some(last) => self.bitmap.rank(u32::try_from(last).unwrap_or(u32::MAX)),
+        }
+    }
+
+    // Fraction of the ids below `universe` that are in the filter
+    pub fn selectivity(&self, universe: usize) -> f32 {
+        if universe == 0 {
+            return 0.;
+        }
+        (self.len_below(universe) as f64 / universe as f64) as f32
+    }
+
     pub fn and(&self, other: &Filter) -> Filter {
-        Filter::from_bitmap(self.bitmap.clone() & other.bitmap.clone())
+        Filter::from_bitmap(&self.bitmap & &other.bitmap)
     }
 
     pub fn or(&self, other: &Filter) -> Filter {
-        Filter::from_bitmap(self.bitmap.clone() | other.bitmap.clone())
+        Filter::from_bitmap(&self.bitmap | &other.bitmap)
+    }
+
+    pub fn xor(&self, other: &Filter) -> Filter {
+        Filter::from_bitmap(&self.bitmap ^ &other.bitmap)
+    }
+
+    pub fn and_not(&self, other: &Filter) -> Filter {
+        Filter::from_bitmap(&self.bitmap - &other.bitmap)
+    }
+
+    // Ids below `universe` that aren't in the filter
+    pub fn not(&self, universe: u32) -> Filter {
+        Filter::from_range(0..universe).and_not(self)
+    }
+
+    // In-place versions, which update the filter's own bitmap
+    pub fn and_with(&mut self, other: &Filter) {
+        self.bitmap &= &other.bitmap;
+    }
+
+    pub fn or_with(&mut self, other: &Filter) {
+        self.bitmap |= &other.bitmap;
+    }
+
+    pub fn xor_with(&mut self, other: &Filter) {
+        self.bitmap ^= &other.bitmap;
+    }
+
+    pub fn and_not_with(&mut self, other: &Filter) {
+        self.bitmap -= &other.bitmap;
+    }
+
+    pub fn not_with(&mut self, universe: u32) {
+        self.bitmap.remove_range(universe..);
+        self.bitmap ^= Filter::from_range(0..universe).bitmap;
+    }
+
+    // Filters are serialized in roaring's portable format, so they can be
+    // cached and read by other roaring implementations
+    pub fn serialized_size(&self) -> usize {
+        self.bitmap.serialized_size()
+    }
+
+    pub fn serialize_into<W: Write>(&self, writer: W) {
+        self.bitmap
+            .serialize_into(writer)
+            .expect("couldn't serialize filter");
+    }
+
+    pub fn deserialize_from<R: Read>(reader: R) -> Filter {
+        Filter::from_bitmap(
+            RoaringBitmap::deserialize_from(reader).expect("couldn't deserialize filter"),
+        )
     }
 }
 
@@ -69,4 +179,37 @@ mod tests {
 
         assert_eq!(filter1, expected)
     }
+
+    #[test]
+    fn set_algebra() {
+        let mut left = Filter::new();
+        let mut right = Filter::new();
+        left.insert_many(&[1, 2, 3]);
+        right.insert_many(&[3, 4]);
+
+        assert_eq!(left.xor(&right).iter().collect::<Vec<u32>>(), [1, 2, 4]);
+        assert_eq!(left.and_not(&right).iter().collect::<Vec<u32>>(), [1, 2]);
+        assert_eq!(left.not(6).iter().collect::<Vec<u32>>(), [0, 4, 5]);
+        assert_eq!(left.selectivity(6), 0.5);
+        assert_eq!(left.len_below(3), 2);
+        assert_eq!(left.len_below(0), 0);
+        assert_eq!(left.len_below(1 << 40), 3);
+
+        let mut inplace = Filter::from_range(0..4);
+        inplace.and_not_with(&left);
+        assert_eq!(inplace.iter().collect::<Vec<u32>>(), [0]);
+        inplace.or_with(&right);
+        inplace.not_with(5);
+        assert_eq!(inplace.iter().collect::<Vec<u32>>(), [1, 2]);
+    }
+
+    #[test]
+    fn serialize() {
+        let filter = Filter::from_range(10..20).or(&Filter::from_range(1000..1005));
+
+        let mut bytes = Vec::with_capacity(filter.serialized_size());
+        filter.serialize_into(&mut bytes);
+
+        assert_eq!(Filter::deserialize_from(&bytes[..]), filter);
+    }
 }
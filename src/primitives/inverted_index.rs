@@ -1,7 +1,33 @@
+use super::filter::Filter;
 use super::posting_list::PostingList;
+use super::sparse_vector::SparseVector;
+use super::vector_table::{push_bounded, Metric};
+
+use roaring::RoaringBitmap;
 
 use std::collections::HashMap;
 
+use min_max_heap::MinMaxHeap;
+use ordered_float::OrderedFloat;
+
+// How `InvertedIndex::to_filter` combines the postings of several keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOp {
+    // Ids with every key
+    And,
+    // Ids with any key
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexStats {
+    pub keys: usize,
+    // Ids summed over every key's postings
+    pub postings: u64,
+    pub longest: u64,
+    pub mean: f32,
+}
+
 #[derive(Debug)]
 pub struct InvertedIndex {
     postings: HashMap<u32, PostingList>,
@@ -15,25 +41,196 @@ impl InvertedIndex {
     }
 
     pub fn insert(&mut self, key: u32, value: u32) {
-        if !self.postings.contains_key(&key) {
-            self.postings.insert(key, PostingList::new());
-        }
-        let postings = self.postings.get_mut(&key);
-        postings.unwrap().insert(value);
+        self.postings_mut(key).insert(value);
     }
 
     pub fn insert_many(&mut self, key: u32, value: &[u32]) {
-        if !self.postings.contains_key(&key) {
-            self.postings.insert(key, PostingList::new());
+        self.postings_mut(key).insert_many(value);
+    }
+
+    pub fn insert_weighted(&mut self, key: u32, value: u32, weight: f32) {
+        self.postings_mut(key).insert_weighted(value, weight);
+    }
+
+    pub fn get(&self, key: u32) -> Option<&PostingList> {
+        self.postings.get(&key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u32, &PostingList)> {
+        self.postings.iter()
+    }
+
+    // Keys with at least one id, in no particular order
+    pub fn keys(&self) -> impl Iterator<Item = u32> + '_ {
+        self.postings.keys().copied()
+    }
+
+    // Removes the id from the key's postings, dropping the key when it has
+    // no ids left. Returns whether the id was there
+    pub fn remove(&mut self, key: u32, id: u32) -> bool {
+        let postings = match self.postings.get_mut(&key) {
+            Some(postings) => postings,
+            None => return false,
+        };
+        let removed = postings.remove(id);
+        if postings.is_empty() {
+            self.postings.remove(&key);
+        }
+        removed
+    }
+
+    // Number of ids with the key
+    pub fn cardinality(&self, key: u32) -> u64 {
+        self.postings.get(&key).map_or(0, |postings| postings.len())
+    }
+
+    pub fn stats(&self) -> IndexStats {
+        let lengths: Vec<u64> = self
+            .postings
+            .values()
+            .map(|postings| postings.len())
+            .collect();
+        let postings: u64 = lengths.iter().sum();
+        IndexStats {
+            keys: lengths.len(),
+            postings,
+            longest: lengths.iter().copied().max().unwrap_or(0),
+            mean: if lengths.is_empty() {
+                0.
+            } else {
+                postings as f32 / lengths.len() as f32
+            },
+        }
+    }
+
+    // Combines the postings of the keys into a filter. Intersections start
+    // from the shortest postings, and are empty if any key is missing or
+    // there are no keys at all
+    pub fn to_filter(&self, keys: &[u32], op: FilterOp) -> Filter {
+        let mut postings: Vec<Option<&PostingList>> =
+            keys.iter().map(|key| self.postings.get(key)).collect();
+
+        let bitmap = match op {
+            FilterOp::Or => postings
+                .into_iter()
+                .flatten()
+                .map(|postings| postings.bitmap())
+                .fold(RoaringBitmap::new(), |union, bitmap| union | bitmap),
+            FilterOp::And => {
+                if postings.is_empty() || postings.iter().any(|postings| postings.is_none()) {
+                    RoaringBitmap::new()
+                } else {
+                    postings.sort_by_key(|postings| postings.unwrap().len());
+                    let mut lists = postings.into_iter().flatten();
+                    let mut intersection = lists.next().unwrap().bitmap().clone();
+                    for postings in lists {
+                        intersection &= postings.bitmap();
+                    }
+                    intersection
+                }
+            }
+        };
+        Filter::from_bitmap(bitmap)
+    }
+
+    // Inner products of the query with every id that shares a key with it,
+    // treating keys as terms and posting weights as the ids' term weights
+    pub fn inner_products(
+        &self,
+        query: &SparseVector,
+        filter: Option<&Filter>,
+    ) -> HashMap<usize, f32> {
+        let mut scores = HashMap::new();
+
+        for (term, weight) in query.iter() {
+            let postings = match self.postings.get(&term) {
+                Some(postings) => postings,
+                None => continue,
+            };
+
+            for (id, row_weight) in postings.iter() {
+                if filter.is_some_and(|filter| !filter.contains(id)) {
+                    continue;
+                }
+                *scores.entry(id as usize).or_insert(0.) += weight * row_weight;
+            }
         }
-        let postings = self.postings.get_mut(&key);
-        postings.unwrap().insert_many(value);
+        scores
+    }
+
+    // Top-k ids by inner product, accumulated term-at-a-time from the term
+    // with the largest possible contribution down. Once no id that hasn't
+    // been seen yet could beat the current k-th score with the terms that
+    // are left (MaxScore), the rest of the terms only update seen ids.
+    // Pruning needs every contribution to be non-negative, so it's skipped
+    // when the query or the postings it touches have negative weights
+    pub fn query(
+        &self,
+        query: &SparseVector,
+        k: usize,
+        filter: Option<&Filter>,
+    ) -> Vec<(Metric, usize)> {
+        let mut terms: Vec<(&PostingList, f32, f32)> = query
+            .iter()
+            .filter_map(|(term, weight)| {
+                let postings = self.postings.get(&term)?;
+                Some((postings, weight, weight * postings.max_weight))
+            })
+            .collect();
+        terms.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        let prunable = k > 0
+            && terms
+                .iter()
+                .all(|(postings, weight, _)| *weight >= 0. && postings.min_weight >= 0.);
+        let mut remaining: f32 = terms.iter().map(|(_, _, bound)| bound).sum();
+
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        let mut admitting = true;
+
+        for (postings, weight, bound) in terms {
+            if admitting && prunable && scores.len() >= k {
+                admitting = remaining > kth_largest(&scores, k);
+            }
+
+            for (id, row_weight) in postings.iter() {
+                if filter.is_some_and(|filter| !filter.contains(id)) {
+                    continue;
+                }
+                if admitting {
+                    *scores.entry(id).or_insert(0.) += weight * row_weight;
+                } else if let Some(score) = scores.get_mut(&id) {
+                    *score += weight * row_weight;
+                }
+            }
+            remaining -= bound;
+        }
+
+        let mut heap = MinMaxHeap::with_capacity(k);
+        for (id, score) in scores {
+            push_bounded(&mut heap, k, false, (OrderedFloat(score), id as usize));
+        }
+        heap.into_vec_desc()
+    }
+
+    fn postings_mut(&mut self, key: u32) -> &mut PostingList {
+        self.postings.entry(key).or_insert_with(PostingList::new)
     }
 }
 
+fn kth_largest(scores: &HashMap<u32, f32>, k: usize) -> f32 {
+    let mut values: Vec<f32> = scores.values().copied().collect();
+    let (_, kth, _) = values.select_nth_unstable_by(k - 1, |a, b| b.total_cmp(a));
+    *kth
+}
+
 #[cfg(test)]
 mod tests {
-    use super::InvertedIndex;
+    use super::{FilterOp, InvertedIndex};
+    use crate::primitives::filter::Filter;
+    use crate::primitives::sparse_vector::SparseVector;
+    use ndarray_rand::rand::rngs::StdRng;
+    use ndarray_rand::rand::{Rng, SeedableRng};
 
     #[test]
     fn insert_many() {
@@ -46,6 +243,64 @@ mod tests {
 
         let posting = index.postings.get(&key).unwrap();
 
-        assert_eq!(posting.ids, ids)
+        assert_eq!(posting.ids().collect::<Vec<u32>>(), ids)
+    }
+
+    #[test]
+    fn remove_and_to_filter() {
+        let mut index = InvertedIndex::new();
+        index.insert_many(1, &[1, 2, 3, 4]);
+        index.insert_many(2, &[3, 4, 5]);
+        index.insert_many(3, &[9]);
+
+        let mut expected = Filter::new();
+        expected.insert_many(&[3, 4]);
+        assert_eq!(index.to_filter(&[1, 2], FilterOp::And), expected);
+        assert!(index.to_filter(&[1, 7], FilterOp::And).is_empty());
+
+        expected.insert_many(&[1, 2, 5]);
+        assert_eq!(index.to_filter(&[1, 2, 7], FilterOp::Or), expected);
+
+        assert!(index.remove(3, 9));
+        assert!(!index.remove(3, 9));
+        assert_eq!(index.cardinality(3), 0);
+        let mut keys: Vec<u32> = index.keys().collect();
+        keys.sort();
+        assert_eq!(keys, [1, 2]);
+
+        let stats = index.stats();
+        assert_eq!(stats.keys, 2);
+        assert_eq!(stats.postings, 7);
+        assert_eq!(stats.longest, 4);
+        assert_eq!(stats.mean, 3.5);
+    }
+
+    #[test]
+    fn query() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut index = InvertedIndex::new();
+        for id in 0..2000 {
+            for _ in 0..5 {
+                let term = rng.gen_range(0..100);
+                index.insert_weighted(term, id, rng.gen::<f32>());
+            }
+        }
+
+        let pairs: Vec<(u32, f32)> = (0..8).map(|term| (term * 10, rng.gen::<f32>())).collect();
+        let query = SparseVector::new(&pairs);
+
+        let mut exhaustive: Vec<(f32, usize)> = index
+            .inner_products(&query, None)
+            .into_iter()
+            .map(|(id, score)| (score, id))
+            .collect();
+        exhaustive.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let results = index.query(&query, 10, None);
+        assert_eq!(results.len(), 10);
+        for (result, expected) in results.iter().zip(&exhaustive) {
+            assert_eq!(result.1, expected.1);
+            assert!((result.0 .0 - expected.0).abs() < 1e-5);
+        }
     }
 }
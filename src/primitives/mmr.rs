@@ -0,0 +1,80 @@
+use crate::primitives::vector_table::Metric;
+
+use ordered_float::OrderedFloat;
+
+// Re-orders candidates by maximal marginal relevance, picking k of them one
+// at a time to balance closeness to the query against closeness to results
+// already picked. `lambda` of 1 keeps the original order, 0 only diversifies.
+//
+// Candidates hold their metric to the query, and `metrics_from(i)` gives the
+// metric from candidate i to every candidate. When `asc` is true the metric is
+// a distance, which is negated to treat it as a similarity.
+pub fn select_mmr<F: FnMut(usize) -> Vec<f32>>(
+    candidates: &[(Metric, usize)],
+    k: usize,
+    lambda: f32,
+    asc: bool,
+    mut metrics_from: F,
+) -> Vec<(Metric, usize)> {
+    let sign = if asc { -1. } else { 1. };
+
+    // Highest similarity between each candidate and anything selected so far
+    let mut redundancy = vec![f32::NEG_INFINITY; candidates.len()];
+    let mut selected = vec![false; candidates.len()];
+    let mut results = Vec::with_capacity(k.min(candidates.len()));
+
+    while results.len() < k.min(candidates.len()) {
+        let (best, _) = candidates
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !selected[*i])
+            .map(|(i, (metric, _))| {
+                let relevance = sign * metric.0;
+                let score = if results.is_empty() {
+                    relevance
+                } else {
+                    lambda * relevance - (1. - lambda) * redundancy[i]
+                };
+                (i, OrderedFloat(score))
+            })
+            .max_by_key(|(_, score)| *score)
+            .unwrap();
+
+        selected[best] = true;
+        results.push(candidates[best]);
+
+        for (i, metric) in metrics_from(best).iter().enumerate() {
+            redundancy[i] = redundancy[i].max(sign * metric);
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::select_mmr;
+    use ordered_float::OrderedFloat;
+
+    #[test]
+    fn skips_near_duplicates() {
+        // Points on a line, where 0 and 1 are almost the same
+        let points = [0.0f32, 0.01, 1.0];
+        let query = 0.1;
+        let candidates: Vec<_> = points
+            .iter()
+            .enumerate()
+            .map(|(pos, p)| (OrderedFloat((p - query).abs()), pos))
+            .collect();
+
+        let metrics_from = |i: usize| points.iter().map(|p| (p - points[i]).abs()).collect();
+
+        let results = select_mmr(&candidates, 2, 1., true, metrics_from);
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        assert_eq!(positions, [1, 0]);
+
+        let results = select_mmr(&candidates, 2, 0.5, true, metrics_from);
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        assert_eq!(positions, [1, 2]);
+    }
+}
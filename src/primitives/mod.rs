@@ -1,5 +1,16 @@
+pub mod aggregation;
+pub mod attributes;
+pub mod binary_table;
+pub mod exploration;
 pub mod filter;
 pub mod inverted_index;
+pub mod mmr;
+pub mod planner;
 pub mod posting_list;
+pub mod quantized_table;
+pub mod quantizer;
+pub mod sparse_table;
+pub mod sparse_vector;
 pub mod vector;
+pub mod vector_file;
 pub mod vector_table;
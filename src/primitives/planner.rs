@@ -0,0 +1,124 @@
+use super::filter::Filter;
+
+// How a filtered query finds its results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryPlan {
+    // Score only the rows in the filter, for very selective filters
+    MatchingRows,
+    // Score every row, skipping rows outside the filter as they come up.
+    // There's no ANN index to traverse yet, so this is a brute-force
+    // filtered scan standing in for filtered ANN traversal
+    FilteredScan,
+    // Fetch more than k results without the filter, then drop rows outside
+    // it. For broad filters, where most rows pass anyway
+    OverFetch,
+}
+
+// Picks a plan from the number of rows the filter matches
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryPlanner {
+    // Filters matching at most this many rows score them directly
+    pub max_matching_rows: u64,
+    // Filters matching at least this fraction of rows over-fetch
+    pub min_overfetch_selectivity: f32,
+    // Extra results fetched beyond k / selectivity when over-fetching
+    pub overfetch_factor: f32,
+}
+
+impl Default for QueryPlanner {
+    fn default() -> QueryPlanner {
+        QueryPlanner::new()
+    }
+}
+
+impl QueryPlanner {
+    pub fn new() -> QueryPlanner {
+        QueryPlanner {
+            max_matching_rows: 512,
+            min_overfetch_selectivity: 0.5,
+            overfetch_factor: 1.5,
+        }
+    }
+
+    pub fn plan(&self, filter: &Filter, rows: usize) -> QueryStats {
+        let matching_rows = filter.len_below(rows);
+        let selectivity = if rows == 0 {
+            0.
+        } else {
+            (matching_rows as f64 / rows as f64) as f32
+        };
+
+        let plan = if matching_rows <= self.max_matching_rows {
+            QueryPlan::MatchingRows
+        } else if selectivity >= self.min_overfetch_selectivity {
+            QueryPlan::OverFetch
+        } else {
+            QueryPlan::FilteredScan
+        };
+
+        QueryStats {
+            plan,
+            selectivity,
+            matching_rows,
+            rows_scored: 0,
+            fell_back: false,
+        }
+    }
+
+    // Number of unfiltered results to fetch so that about k pass the filter
+    pub fn overfetch_k(&self, k: usize, selectivity: f32) -> usize {
+        (k as f32 * self.overfetch_factor / selectivity.max(f32::EPSILON)).ceil() as usize
+    }
+}
+
+// What a planned query did, for tuning the planner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryStats {
+    pub plan: QueryPlan,
+    pub selectivity: f32,
+    pub matching_rows: u64,
+    pub rows_scored: usize,
+    // Whether over-fetching came up short of k and fell back to a scan
+    pub fell_back: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QueryPlan, QueryPlanner};
+    use crate::primitives::filter::Filter;
+
+    #[test]
+    fn plan() {
+        let planner = QueryPlanner::new();
+        let rows = 10000;
+
+        let narrow = Filter::from_range(0..100);
+        assert_eq!(planner.plan(&narrow, rows).plan, QueryPlan::MatchingRows);
+
+        let medium = Filter::from_range(0..2000);
+        assert_eq!(planner.plan(&medium, rows).plan, QueryPlan::FilteredScan);
+
+        let broad = Filter::from_range(0..8000);
+        let stats = planner.plan(&broad, rows);
+        assert_eq!(stats.plan, QueryPlan::OverFetch);
+        assert_eq!(stats.selectivity, 0.8);
+        assert_eq!(planner.overfetch_k(10, stats.selectivity), 19);
+
+        // Counts stay exact on tables too large for f32 to tell them apart
+        let rows = 100_000_000;
+        let at_limit = Filter::from_range(0..512);
+        let stats = planner.plan(&at_limit, rows);
+        assert_eq!(stats.matching_rows, 512);
+        assert_eq!(stats.plan, QueryPlan::MatchingRows);
+
+        let past_limit = Filter::from_range(0..513);
+        let stats = planner.plan(&past_limit, rows);
+        assert_eq!(stats.matching_rows, 513);
+        assert_eq!(stats.plan, QueryPlan::FilteredScan);
+
+        // And on tables with more rows than there are u32 ids
+        let stats = planner.plan(&medium, 1 << 33);
+        assert_eq!(stats.matching_rows, 2000);
+        assert_eq!(stats.plan, QueryPlan::FilteredScan);
+    }
+}
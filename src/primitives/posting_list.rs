@@ -1,27 +1,159 @@
-#[derive(Debug, PartialEq)]
+extern crate roaring;
+use roaring::RoaringBitmap;
+
+use super::filter::Filter;
+
+// Ids are kept sorted and deduplicated in a compressed bitmap, and each
+// id's weight is stored at the id's rank among them
+#[derive(Debug, Clone, PartialEq)]
 pub struct PostingList {
-    pub ids: Vec<u32>,
+    ids: RoaringBitmap,
+    weights: Vec<f32>,
+    // Bounds on the weights, used to bound a term's contribution to a score
+    pub max_weight: f32,
+    pub min_weight: f32,
 }
 
 impl PostingList {
     pub fn new() -> PostingList {
         PostingList {
-            ids: Vec::<u32>::new(),
+            ids: RoaringBitmap::new(),
+            weights: Vec::<f32>::new(),
+            max_weight: 0.,
+            min_weight: 0.,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.ids.contains(id)
+    }
+
+    pub fn weight(&self, id: u32) -> Option<f32> {
+        if self.ids.contains(id) {
+            Some(self.weights[self.ids.rank(id) as usize - 1])
+        } else {
+            None
         }
     }
 
     pub fn insert(&mut self, id: u32) {
-        self.ids.extend([id].iter())
+        self.insert_weighted(id, 1.)
     }
 
     pub fn insert_many(&mut self, ids: &[u32]) {
-        self.ids.extend(ids.iter())
+        for id in ids {
+            self.insert(*id);
+        }
+    }
+
+    // Inserting an id that's already present replaces its weight
+    pub fn insert_weighted(&mut self, id: u32, weight: f32) {
+        let rank = self.ids.rank(id) as usize;
+        if self.ids.contains(id) {
+            self.weights[rank - 1] = weight;
+        } else {
+            self.ids.insert(id);
+            self.weights.insert(rank, weight);
+        }
+        self.max_weight = self.max_weight.max(weight);
+        self.min_weight = self.min_weight.min(weight);
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        if !self.ids.contains(id) {
+            return false;
+        }
+        let rank = self.ids.rank(id) as usize;
+        self.ids.remove(id);
+        self.weights.remove(rank - 1);
+        true
+    }
+
+    // Ids in increasing order
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.ids.iter()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, f32)> + '_ {
+        self.ids.iter().zip(self.weights.iter().copied())
+    }
+
+    // Ids in both lists, weighted by the sum of their weights
+    pub fn intersection(&self, other: &PostingList) -> PostingList {
+        self.merge(other, false)
+    }
+
+    // Ids in either list, weighted by the sum of their weights
+    pub fn union(&self, other: &PostingList) -> PostingList {
+        self.merge(other, true)
+    }
+
+    pub(crate) fn bitmap(&self) -> &RoaringBitmap {
+        &self.ids
+    }
+
+    pub fn to_filter(&self) -> Filter {
+        Filter::from_bitmap(self.ids.clone())
+    }
+
+    // Walks both lists in id order, which keeps the ids appended to the
+    // merged list sorted
+    fn merge(&self, other: &PostingList, union: bool) -> PostingList {
+        let mut merged = PostingList::new();
+        let mut left = self.iter().peekable();
+        let mut right = other.iter().peekable();
+
+        loop {
+            match (left.peek().copied(), right.peek().copied()) {
+                (Some((l, lw)), Some((r, rw))) if l == r => {
+                    left.next();
+                    right.next();
+                    merged.push(l, lw + rw);
+                }
+                (Some((l, lw)), Some((r, _))) if l < r => {
+                    left.next();
+                    if union {
+                        merged.push(l, lw);
+                    }
+                }
+                (_, Some((r, rw))) => {
+                    right.next();
+                    if union {
+                        merged.push(r, rw);
+                    }
+                }
+                (Some((l, lw)), None) => {
+                    left.next();
+                    if union {
+                        merged.push(l, lw);
+                    }
+                }
+                (None, None) => break,
+            }
+        }
+        merged
+    }
+
+    fn push(&mut self, id: u32, weight: f32) {
+        self.ids.push(id);
+        self.weights.push(weight);
+        self.max_weight = self.max_weight.max(weight);
+        self.min_weight = self.min_weight.min(weight);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::PostingList;
+    use crate::primitives::filter::Filter;
 
     #[test]
     fn insert_many() {
@@ -36,4 +168,47 @@ mod tests {
 
         assert_eq!(postings, expected)
     }
+
+    #[test]
+    fn insert_weighted() {
+        let mut postings = PostingList::new();
+        postings.insert_weighted(9, -2.);
+        postings.insert_weighted(4, 0.5);
+        postings.insert_weighted(6, 1.5);
+        postings.insert_weighted(4, 0.25);
+
+        let pairs: Vec<(u32, f32)> = postings.iter().collect();
+        assert_eq!(pairs, [(4, 0.25), (6, 1.5), (9, -2.)]);
+        assert_eq!(postings.weight(6), Some(1.5));
+        assert_eq!(postings.weight(5), None);
+        assert_eq!(postings.max_weight, 1.5);
+        assert_eq!(postings.min_weight, -2.);
+
+        assert!(postings.remove(6));
+        assert!(!postings.remove(6));
+        let pairs: Vec<(u32, f32)> = postings.iter().collect();
+        assert_eq!(pairs, [(4, 0.25), (9, -2.)]);
+    }
+
+    #[test]
+    fn intersection_and_union() {
+        let mut left = PostingList::new();
+        left.insert_weighted(1, 1.);
+        left.insert_weighted(5, 2.);
+        left.insert_weighted(8, 3.);
+
+        let mut right = PostingList::new();
+        right.insert_weighted(5, 0.5);
+        right.insert_weighted(7, 4.);
+
+        let pairs: Vec<(u32, f32)> = left.intersection(&right).iter().collect();
+        assert_eq!(pairs, [(5, 2.5)]);
+
+        let pairs: Vec<(u32, f32)> = left.union(&right).iter().collect();
+        assert_eq!(pairs, [(1, 1.), (5, 2.5), (7, 4.), (8, 3.)]);
+
+        let mut expected = Filter::new();
+        expected.insert_many(&[1, 5, 8]);
+        assert_eq!(left.to_filter(), expected);
+    }
 }
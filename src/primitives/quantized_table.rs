@@ -0,0 +1,176 @@
+use crate::prelude::*;
+use crate::primitives::filter::Filter;
+use crate::primitives::mmr::select_mmr;
+use crate::primitives::quantizer::ScalarQuantizer;
+use crate::primitives::vector_table::{push_bounded, Metric};
+
+use min_max_heap::MinMaxHeap;
+use ordered_float::OrderedFloat;
+
+// Stores vectors as scalar-quantized codes, packed back to back
+#[derive(Debug, PartialEq)]
+pub struct QuantizedTable {
+    pub quantizer: ScalarQuantizer,
+    codes: Vec<u8>,
+}
+
+impl QuantizedTable {
+    pub fn new(quantizer: ScalarQuantizer) -> QuantizedTable {
+        QuantizedTable {
+            quantizer,
+            codes: Vec::<u8>::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.codes.len() / self.quantizer.code_len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    pub fn insert(&mut self, vector: &Vector) {
+        let code_len = self.quantizer.code_len();
+        let start = self.codes.len();
+
+        self.codes.resize(start + code_len, 0);
+        self.quantizer
+            .encode_into(vector, &mut self.codes[start..start + code_len]);
+    }
+
+    pub fn insert_many(&mut self, vectors: &[Vector]) {
+        self.codes
+            .reserve(vectors.len() * self.quantizer.code_len());
+        for vector in vectors {
+            self.insert(vector);
+        }
+    }
+
+    pub fn get(&self, pos: usize) -> Option<Vector> {
+        self.codes
+            .chunks_exact(self.quantizer.code_len())
+            .nth(pos)
+            .map(|code| self.quantizer.decode(code))
+    }
+
+    // Approximate inner products scored directly against the codes, best first
+    pub fn top_k_inner_product(&self, vector: &Vector, k: usize) -> Vec<(Metric, usize)> {
+        let scorer = self.quantizer.inner_product_scorer(vector);
+        let mut heap: MinMaxHeap<(Metric, usize)> = MinMaxHeap::with_capacity(k);
+
+        for (pos, code) in self
+            .codes
+            .chunks_exact(self.quantizer.code_len())
+            .enumerate()
+        {
+            let element = (OrderedFloat(scorer.score(code)), pos);
+            if heap.len() >= k {
+                heap.push_pop_min(element);
+            } else {
+                heap.push(element);
+            }
+        }
+
+        heap.into_vec_desc()
+    }
+
+    // Fetches the best `fetch_k` rows by approximate inner product, then picks
+    // k of them by maximal marginal relevance between the decoded vectors
+    pub fn mmr_top_k_inner_product(
+        &self,
+        vector: &Vector,
+        k: usize,
+        fetch_k: usize,
+        lambda: f32,
+    ) -> Vec<(Metric, usize)> {
+        let candidates = self.top_k_inner_product(vector, fetch_k.max(k));
+
+        let mut pool = Matrix::zeros((candidates.len(), self.quantizer.dim()));
+        for (mut row, (_, pos)) in pool.rows_mut().into_iter().zip(&candidates) {
+            row.assign(&self.get(*pos).unwrap());
+        }
+
+        select_mmr(&candidates, k, lambda, false, |i| {
+            pool.dot(&pool.row(i)).to_vec()
+        })
+    }
+
+    // Rows with an approximate inner product of at least `threshold`,
+    // best first, keeping only the best `limit` when a limit is given
+    pub fn range_inner_product(
+        &self,
+        vector: &Vector,
+        threshold: f32,
+        filter: Option<&Filter>,
+        limit: Option<usize>,
+    ) -> Vec<(Metric, usize)> {
+        let scorer = self.quantizer.inner_product_scorer(vector);
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut heap = MinMaxHeap::new();
+
+        for (pos, code) in self
+            .codes
+            .chunks_exact(self.quantizer.code_len())
+            .enumerate()
+        {
+            if filter.is_some_and(|filter| !filter.contains(pos as u32)) {
+                continue;
+            }
+
+            let score = scorer.score(code);
+            if score >= threshold {
+                push_bounded(&mut heap, limit, false, (OrderedFloat(score), pos));
+            }
+        }
+
+        heap.into_vec_desc()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuantizedTable;
+    use crate::prelude::*;
+    use crate::primitives::quantizer::{QuantizerBits, ScalarQuantizer};
+    use crate::primitives::vector::random_vector;
+
+    #[test]
+    fn insert_many() {
+        let dim = 64;
+        let vectors: Vec<Vector> = (0..100).map(|_| random_vector(dim)).collect();
+        let quantizer = ScalarQuantizer::train(&vectors, QuantizerBits::Four);
+
+        let mut table = QuantizedTable::new(quantizer.clone());
+        table.insert_many(&vectors);
+
+        assert_eq!(table.len(), 100);
+        assert_eq!(
+            table.get(12),
+            Some(quantizer.decode(&quantizer.encode(&vectors[12])))
+        );
+        assert_eq!(table.get(100), None);
+    }
+
+    #[test]
+    fn top_k_inner_product() {
+        let dim = 64;
+        let k = 10;
+        let vectors: Vec<Vector> = (0..1000).map(|_| random_vector(dim)).collect();
+        let quantizer = ScalarQuantizer::train(&vectors, QuantizerBits::Eight);
+
+        let mut table = QuantizedTable::new(quantizer);
+        table.insert_many(&vectors);
+
+        let query = random_vector(dim);
+        let results = table.top_k_inner_product(&query, k);
+
+        let best = vectors
+            .iter()
+            .map(|v| query.dot(v))
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        assert_eq!(results.len(), k);
+        assert!((results[0].0 .0 - best).abs() / best < 0.01);
+    }
+}
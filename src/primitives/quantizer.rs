@@ -0,0 +1,203 @@
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantizerBits {
+    Eight,
+    Four,
+}
+
+impl QuantizerBits {
+    fn levels(&self) -> f32 {
+        match self {
+            QuantizerBits::Eight => 255.,
+            QuantizerBits::Four => 15.,
+        }
+    }
+}
+
+// Maps each dimension onto evenly spaced levels between
+// the min and max values seen for it during training
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalarQuantizer {
+    pub bits: QuantizerBits,
+    mins: Vector,
+    scales: Vector,
+}
+
+impl ScalarQuantizer {
+    pub fn train(vectors: &[Vector], bits: QuantizerBits) -> ScalarQuantizer {
+        assert!(!vectors.is_empty(), "Can't train quantizer without vectors");
+
+        let dim = vectors[0].len();
+        let mut mins = Vector::from_elem(dim, f32::INFINITY);
+        let mut maxs = Vector::from_elem(dim, f32::NEG_INFINITY);
+
+        for vector in vectors {
+            assert!(vector.len() == dim, "Vector dim doesn't match");
+            mins.zip_mut_with(vector, |min, value| *min = min.min(*value));
+            maxs.zip_mut_with(vector, |max, value| *max = max.max(*value));
+        }
+
+        let scales = (&maxs - &mins) / bits.levels();
+
+        ScalarQuantizer { bits, mins, scales }
+    }
+
+    pub fn dim(&self) -> usize {
+        self.mins.len()
+    }
+
+    // Bytes per encoded vector
+    pub fn code_len(&self) -> usize {
+        match self.bits {
+            QuantizerBits::Eight => self.dim(),
+            QuantizerBits::Four => self.dim().div_ceil(2),
+        }
+    }
+
+    pub fn encode(&self, vector: &Vector) -> Vec<u8> {
+        let mut code = vec![0; self.code_len()];
+        self.encode_into(vector, &mut code);
+        code
+    }
+
+    pub fn encode_into(&self, vector: &Vector, code: &mut [u8]) {
+        assert!(vector.len() == self.dim(), "Vector dim doesn't match");
+
+        let levels = self.bits.levels();
+        let quantized = vector
+            .iter()
+            .zip(self.mins.iter().zip(self.scales.iter()))
+            .map(|(value, (min, scale))| {
+                if *scale > 0. {
+                    ((value - min) / scale).round().clamp(0., levels) as u8
+                } else {
+                    0
+                }
+            });
+
+        match self.bits {
+            QuantizerBits::Eight => {
+                for (byte, level) in code.iter_mut().zip(quantized) {
+                    *byte = level;
+                }
+            }
+            QuantizerBits::Four => {
+                // Even dimensions go in the low nibble
+                code.fill(0);
+                for (pos, level) in quantized.enumerate() {
+                    code[pos / 2] |= level << (4 * (pos % 2));
+                }
+            }
+        }
+    }
+
+    pub fn decode(&self, code: &[u8]) -> Vector {
+        let mut vector = self.mins.clone();
+        for (pos, value) in vector.iter_mut().enumerate() {
+            *value += self.scales[pos] * self.level(code, pos) as f32;
+        }
+        vector
+    }
+
+    fn level(&self, code: &[u8], pos: usize) -> u8 {
+        match self.bits {
+            QuantizerBits::Eight => code[pos],
+            QuantizerBits::Four => (code[pos / 2] >> (4 * (pos % 2))) & 0x0f,
+        }
+    }
+
+    // Folds the query into per-level weights, so inner products
+    // can be scored directly against the codes without decoding
+    pub fn inner_product_scorer(&self, query: &Vector) -> InnerProductScorer {
+        assert!(query.len() == self.dim(), "Vector dim doesn't match");
+
+        InnerProductScorer {
+            bits: self.bits,
+            weights: query * &self.scales,
+            bias: query.dot(&self.mins),
+        }
+    }
+}
+
+// q.x = q.min + sum_i (q_i * scale_i) * level_i
+#[derive(Debug)]
+pub struct InnerProductScorer {
+    bits: QuantizerBits,
+    weights: Vector,
+    bias: f32,
+}
+
+impl InnerProductScorer {
+    pub fn score(&self, code: &[u8]) -> f32 {
+        let weights = self.weights.as_slice().unwrap();
+
+        let sum: f32 = match self.bits {
+            QuantizerBits::Eight => weights
+                .iter()
+                .zip(code.iter())
+                .map(|(weight, level)| weight * *level as f32)
+                .sum(),
+            QuantizerBits::Four => weights
+                .chunks(2)
+                .zip(code.iter())
+                .map(|(pair, byte)| {
+                    let low = pair[0] * (byte & 0x0f) as f32;
+                    let high = pair.get(1).map_or(0., |w| w * (byte >> 4) as f32);
+                    low + high
+                })
+                .sum(),
+        };
+
+        self.bias + sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QuantizerBits, ScalarQuantizer};
+    use crate::prelude::*;
+    use crate::primitives::vector::random_vector;
+
+    #[test]
+    fn round_trip() {
+        let dim = 33;
+        let vectors: Vec<Vector> = (0..100).map(|_| random_vector(dim)).collect();
+
+        for (bits, tolerance) in [(QuantizerBits::Eight, 0.003), (QuantizerBits::Four, 0.04)] {
+            let quantizer = ScalarQuantizer::train(&vectors, bits);
+            let code = quantizer.encode(&vectors[7]);
+            let decoded = quantizer.decode(&code);
+
+            assert_eq!(code.len(), quantizer.code_len());
+            for (a, b) in decoded.iter().zip(vectors[7].iter()) {
+                assert!((a - b).abs() <= tolerance, "{} != {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn constant_dimension() {
+        let vectors = vec![Vector::from(vec![1., 0.]), Vector::from(vec![1., 1.])];
+        let quantizer = ScalarQuantizer::train(&vectors, QuantizerBits::Eight);
+
+        assert_eq!(quantizer.decode(&quantizer.encode(&vectors[1])), vectors[1]);
+    }
+
+    #[test]
+    fn inner_product_scorer_matches_decoded() {
+        let dim = 31;
+        let vectors: Vec<Vector> = (0..100).map(|_| random_vector(dim)).collect();
+        let query = random_vector(dim);
+
+        for bits in [QuantizerBits::Eight, QuantizerBits::Four] {
+            let quantizer = ScalarQuantizer::train(&vectors, bits);
+            let scorer = quantizer.inner_product_scorer(&query);
+
+            let code = quantizer.encode(&vectors[3]);
+            let expected = query.dot(&quantizer.decode(&code));
+
+            assert!((scorer.score(&code) - expected).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use super::filter::Filter;
+use super::inverted_index::InvertedIndex;
+use super::sparse_vector::SparseVector;
+use super::vector_table::Metric;
+
+use std::collections::HashMap;
+
+// Sparse term-weight vectors, with an inverted index from each term to the
+// rows that have it so queries only touch rows sharing a term with them
+#[derive(Debug)]
+pub struct SparseTable {
+    vectors: Vec<SparseVector>,
+    pub index: InvertedIndex,
+}
+
+impl Default for SparseTable {
+    fn default() -> SparseTable {
+        SparseTable::new()
+    }
+}
+
+impl SparseTable {
+    pub fn new() -> SparseTable {
+        SparseTable {
+            vectors: Vec::<SparseVector>::new(),
+            index: InvertedIndex::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    pub fn get(&self, pos: usize) -> Option<&SparseVector> {
+        self.vectors.get(pos)
+    }
+
+    pub fn insert(&mut self, vector: &SparseVector) {
+        let id = self.vectors.len() as u32;
+        for (term, weight) in vector.iter() {
+            self.index.insert_weighted(term, id, weight);
+        }
+        self.vectors.push(vector.clone());
+    }
+
+    pub fn insert_many(&mut self, vectors: &[SparseVector]) {
+        for vector in vectors {
+            self.insert(vector);
+        }
+    }
+
+    // Inner products with every row that shares a term with the query.
+    // Other rows score zero
+    pub fn inner_products(
+        &self,
+        query: &SparseVector,
+        filter: Option<&Filter>,
+    ) -> HashMap<usize, f32> {
+        self.index.inner_products(query, filter)
+    }
+
+    pub fn top_k_inner_product(
+        &self,
+        query: &SparseVector,
+        k: usize,
+        filter: Option<&Filter>,
+    ) -> Vec<(Metric, usize)> {
+        self.index.query(query, k, filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SparseTable;
+    use crate::primitives::filter::Filter;
+    use crate::primitives::sparse_vector::SparseVector;
+
+    #[test]
+    fn top_k_inner_product() {
+        let mut table = SparseTable::new();
+        table.insert_many(&[
+            SparseVector::new(&[(1, 1.), (2, 1.)]),
+            SparseVector::new(&[(2, 3.)]),
+            SparseVector::new(&[(3, 5.)]),
+            SparseVector::new(&[(1, 2.), (2, 0.5)]),
+        ]);
+
+        let query = SparseVector::new(&[(1, 1.), (2, 1.)]);
+        let results = table.top_k_inner_product(&query, 2, None);
+        assert_eq!(results[0].1, 1);
+        assert_eq!(results[1].1, 3);
+        assert_eq!(results[1].0 .0, 2.5);
+
+        let mut filter = Filter::new();
+        filter.insert_many(&[0, 2]);
+        let results = table.top_k_inner_product(&query, 2, Some(&filter));
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        assert_eq!(positions, [0]);
+    }
+}
@@ -0,0 +1,85 @@
+// Term weights keyed by term id, kept sorted by term
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SparseVector {
+    terms: Vec<u32>,
+    weights: Vec<f32>,
+}
+
+impl SparseVector {
+    // Weights for repeated terms are added together
+    pub fn new(pairs: &[(u32, f32)]) -> SparseVector {
+        let mut pairs = pairs.to_vec();
+        pairs.sort_by_key(|(term, _)| *term);
+
+        let mut vector = SparseVector::default();
+        for (term, weight) in pairs {
+            if vector.terms.last() == Some(&term) {
+                *vector.weights.last_mut().unwrap() += weight;
+            } else {
+                vector.terms.push(term);
+                vector.weights.push(weight);
+            }
+        }
+        vector
+    }
+
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn terms(&self) -> &[u32] {
+        &self.terms
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, f32)> + '_ {
+        self.terms.iter().copied().zip(self.weights.iter().copied())
+    }
+
+    pub fn get(&self, term: u32) -> Option<f32> {
+        let pos = self.terms.binary_search(&term).ok()?;
+        Some(self.weights[pos])
+    }
+
+    // Merges the two sorted term lists
+    pub fn dot(&self, other: &SparseVector) -> f32 {
+        let (mut i, mut j) = (0, 0);
+        let mut sum = 0.;
+
+        while i < self.terms.len() && j < other.terms.len() {
+            match self.terms[i].cmp(&other.terms[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    sum += self.weights[i] * other.weights[j];
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SparseVector;
+
+    #[test]
+    fn new_and_dot() {
+        let a = SparseVector::new(&[(7, 1.), (2, 0.5), (7, 1.)]);
+        let b = SparseVector::new(&[(2, 2.), (3, 4.), (7, 0.25)]);
+
+        assert_eq!(a.terms(), [2, 7]);
+        assert_eq!(a.get(7), Some(2.));
+        assert_eq!(a.get(3), None);
+        assert_eq!(a.dot(&b), 1.5);
+    }
+}
@@ -11,3 +11,54 @@ pub fn random_vector(dim: usize) -> Vector {
     let vector = Vector::random((dim,), dist);
     vector
 }
+
+pub fn random_binary_vector(dim: usize) -> BinaryVector {
+    let words = binary_words(dim);
+    let dist = Uniform::new_inclusive(0, u64::MAX);
+    let mut vector = BinaryVector::random((words,), dist);
+
+    // Clear any padding bits past the last dimension
+    if !dim.is_multiple_of(64) {
+        vector[words - 1] &= (1 << (dim % 64)) - 1;
+    }
+    vector
+}
+
+pub fn binary_words(dim: usize) -> usize {
+    dim.div_ceil(64)
+}
+
+pub fn sign_quantize(vector: &Vector) -> BinaryVector {
+    let mut code = BinaryVector::zeros(binary_words(vector.len()));
+    for (pos, value) in vector.iter().enumerate() {
+        if *value > 0. {
+            code[pos / 64] |= 1 << (pos % 64);
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{random_binary_vector, sign_quantize};
+    use crate::prelude::*;
+
+    #[test]
+    fn sign_quantize_packs_bits() {
+        let mut vector = Vector::from_elem(70, -1.);
+        vector[0] = 1.;
+        vector[65] = 0.5;
+
+        let code = sign_quantize(&vector);
+
+        assert_eq!(code, BinaryVector::from(vec![1, 2]))
+    }
+
+    #[test]
+    fn random_binary_vector_padding() {
+        let code = random_binary_vector(70);
+
+        assert_eq!(code.len(), 2);
+        assert_eq!(code[1] >> 6, 0)
+    }
+}
@@ -0,0 +1,126 @@
+use crate::prelude::*;
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const F32_BYTES: usize = std::mem::size_of::<f32>();
+
+// Fixed-width rows of little-endian f32s on disk, for keeping exact
+// vectors out of memory and reading back a few at a time by position
+#[derive(Debug)]
+pub struct VectorFile {
+    pub dim: usize,
+    len: usize,
+    writer: BufWriter<File>,
+    reader: File,
+}
+
+impl VectorFile {
+    pub fn create(path: &Path, dim: usize) -> VectorFile {
+        let reader = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .expect("couldn't create vector file");
+
+        // A separate handle in append mode, so seeking the reader
+        // doesn't move where the next row gets written
+        let writer = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .expect("couldn't open vector file");
+
+        VectorFile {
+            dim,
+            len: 0,
+            reader,
+            writer: BufWriter::new(writer),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn append(&mut self, vector: &Vector) {
+        assert!(
+            vector.len() == self.dim,
+            "Vector dim doesn't match file dim"
+        );
+
+        for value in vector.iter() {
+            self.writer
+                .write_all(&value.to_le_bytes())
+                .expect("couldn't write vector");
+        }
+        self.len += 1;
+    }
+
+    pub fn read(&mut self, pos: usize) -> Vector {
+        assert!(pos < self.len, "Vector position out of range");
+
+        // Make sure buffered rows are visible to the reader
+        self.writer.flush().expect("couldn't flush vector file");
+
+        let mut bytes = vec![0; self.dim * F32_BYTES];
+        self.reader
+            .seek(SeekFrom::Start((pos * self.dim * F32_BYTES) as u64))
+            .expect("couldn't seek in vector file");
+        self.reader
+            .read_exact(&mut bytes)
+            .expect("couldn't read vector");
+
+        bytes
+            .chunks_exact(F32_BYTES)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VectorFile;
+    use crate::primitives::vector::random_vector;
+
+    #[test]
+    fn append_and_read() {
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        let mut file = VectorFile::create(tmpfile.path(), 16);
+
+        let vectors: Vec<_> = (0..10).map(|_| random_vector(16)).collect();
+        for vector in &vectors {
+            file.append(vector);
+        }
+
+        assert_eq!(file.len(), 10);
+        assert_eq!(file.read(7), vectors[7]);
+        assert_eq!(file.read(0), vectors[0]);
+    }
+
+    #[test]
+    fn interleave_append_and_read() {
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        let mut file = VectorFile::create(tmpfile.path(), 4);
+
+        let vectors: Vec<_> = (0..4).map(|_| random_vector(4)).collect();
+        file.append(&vectors[0]);
+        file.append(&vectors[1]);
+        assert_eq!(file.read(0), vectors[0]);
+
+        file.append(&vectors[2]);
+        assert_eq!(file.read(1), vectors[1]);
+        assert_eq!(file.read(2), vectors[2]);
+
+        file.append(&vectors[3]);
+        for (pos, vector) in vectors.iter().enumerate() {
+            assert_eq!(&file.read(pos), vector);
+        }
+    }
+}
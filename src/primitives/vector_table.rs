@@ -1,25 +1,131 @@
 use crate::distances::Distance;
 use crate::prelude::*;
+use crate::primitives::aggregation::Aggregation;
+use crate::primitives::attributes::Attribute;
+use crate::primitives::exploration::{sample_softmax, Exploration};
+use crate::primitives::filter::Filter;
+use crate::primitives::mmr::select_mmr;
+use crate::primitives::planner::{QueryPlan, QueryPlanner, QueryStats};
 
-use ndarray::Array;
+use ndarray::{s, Array2, ArrayView2, Axis};
 
-use std::cmp::Reverse;
-use std::collections::BinaryHeap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::iter::zip;
 
+use half::slice::HalfFloatSliceExt;
+use half::{bf16, f16};
 use min_max_heap::MinMaxHeap;
+use ndarray_rand::rand::rngs::StdRng;
+use ndarray_rand::rand::{Rng, SeedableRng};
 use ordered_float::OrderedFloat;
 
 pub type Metric = OrderedFloat<f32>;
 
-const CHUNK_SIZE: usize = 4096;
+pub const CHUNK_SIZE: usize = 4096;
+
+// Element type used to store full chunks. Half-precision chunks
+// are widened back to f32 before computing distances
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    F32,
+    F16,
+    BF16,
+}
+
+#[derive(Debug, PartialEq)]
+enum Chunk {
+    F32(Matrix),
+    F16(Array2<f16>),
+    BF16(Array2<bf16>),
+}
+
+impl Chunk {
+    fn narrow(matrix: Matrix, precision: Precision) -> Chunk {
+        let shape = matrix.dim();
+        let values = matrix.as_slice().unwrap();
+
+        match precision {
+            Precision::F32 => Chunk::F32(matrix),
+            Precision::F16 => {
+                let mut narrowed = vec![f16::ZERO; values.len()];
+                narrowed.convert_from_f32_slice(values);
+                Chunk::F16(Array2::from_shape_vec(shape, narrowed).unwrap())
+            }
+            Precision::BF16 => {
+                let mut narrowed = vec![bf16::ZERO; values.len()];
+                narrowed.convert_from_f32_slice(values);
+                Chunk::BF16(Array2::from_shape_vec(shape, narrowed).unwrap())
+            }
+        }
+    }
+
+    fn row(&self, offset: usize) -> Vector {
+        match self {
+            Chunk::F32(matrix) => matrix.row(offset).to_owned(),
+            Chunk::F16(matrix) => matrix.row(offset).mapv(f16::to_f32),
+            Chunk::BF16(matrix) => matrix.row(offset).mapv(bf16::to_f32),
+        }
+    }
+
+    // Returns the chunk as f32, widening into `scratch` if it's stored at lower precision
+    fn widen<'a>(&'a self, scratch: &'a mut Matrix) -> &'a Matrix {
+        let shape = match self {
+            Chunk::F32(matrix) => return matrix,
+            Chunk::F16(matrix) => matrix.dim(),
+            Chunk::BF16(matrix) => matrix.dim(),
+        };
+
+        if scratch.dim() != shape {
+            *scratch = Matrix::zeros(shape);
+        }
+
+        let widened = scratch.as_slice_mut().unwrap();
+        match self {
+            Chunk::F16(matrix) => matrix.as_slice().unwrap().convert_to_f32_slice(widened),
+            Chunk::BF16(matrix) => matrix.as_slice().unwrap().convert_to_f32_slice(widened),
+            Chunk::F32(_) => unreachable!(),
+        }
+
+        scratch
+    }
+}
+
+// Scratch space for scanning the table: distances for one chunk, the chunk
+// widened to f32, and the tail rows stacked into a matrix with their norms.
+// Chunks and the tail differ in shape, so each keeps its own matrix and
+// neither is reallocated from one query to the next
+#[derive(Debug, Default)]
+pub(crate) struct ScanBuffer {
+    dists: Vec<f32>,
+    widened: Matrix,
+    tail: Matrix,
+    norms: Vector,
+}
+
+// Scratch space for matrix queries, which can be kept around
+// and reused to avoid allocating on every query
+#[derive(Debug, Default)]
+pub struct QueryBuffer {
+    scan: ScanBuffer,
+    heap: MinMaxHeap<(Metric, usize)>,
+    pub results: Vec<(Metric, usize)>,
+}
+
+impl QueryBuffer {
+    pub fn new() -> QueryBuffer {
+        QueryBuffer::default()
+    }
+}
 
 #[derive(Debug, PartialEq)]
 pub struct VectorTable {
     pub dim: usize,
     pub chunking: bool,
+    pub precision: Precision,
     vectors: Vec<Vector>,
-    chunks: Vec<Matrix>,
+    chunks: Vec<Chunk>,
+    // Squared L2 norm of each row, one entry per chunk
+    norms: Vec<Vector>,
 }
 
 impl VectorTable {
@@ -27,8 +133,37 @@ impl VectorTable {
         VectorTable {
             dim,
             chunking,
+            precision: Precision::F32,
             vectors: Vec::<Vector>::new(),
-            chunks: Vec::<Matrix>::new(),
+            chunks: Vec::<Chunk>::new(),
+            norms: Vec::<Vector>::new(),
+        }
+    }
+
+    // Only full chunks are narrowed, so reduced precision implies
+    // chunking. Vectors waiting to fill a chunk are still kept as f32
+    pub fn with_precision(dim: usize, precision: Precision) -> VectorTable {
+        VectorTable {
+            precision,
+            ..VectorTable::new(dim, true)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len() * CHUNK_SIZE + self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Chunks stored at reduced precision are widened back to f32
+    pub fn get(&self, pos: usize) -> Option<Vector> {
+        let chunked = self.chunks.len() * CHUNK_SIZE;
+        if pos < chunked {
+            Some(self.chunks[pos / CHUNK_SIZE].row(pos % CHUNK_SIZE))
+        } else {
+            self.vectors.get(pos - chunked).cloned()
         }
     }
 
@@ -51,14 +186,60 @@ impl VectorTable {
         }
     }
 
+    // Inserts rows from a matrix. When the table is chunking, whole chunks
+    // are copied straight out of the matrix without building row vectors
+    pub fn insert_rows(&mut self, rows: ArrayView2<f32>) {
+        assert!(
+            rows.ncols() == self.dim,
+            "Vector dim doesn't match table dim"
+        );
+
+        let mut start = 0;
+        if self.chunking {
+            // Top up any partially filled chunk first
+            let fill = ((CHUNK_SIZE - self.vectors.len()) % CHUNK_SIZE).min(rows.nrows());
+            for row in rows.slice(s![..fill, ..]).rows() {
+                self.vectors.push(row.to_owned());
+            }
+            self.condense_vectors(CHUNK_SIZE);
+            start = fill;
+
+            while self.vectors.is_empty() && rows.nrows() - start >= CHUNK_SIZE {
+                let block = rows.slice(s![start..start + CHUNK_SIZE, ..]);
+                self.push_chunk(block.as_standard_layout().into_owned());
+                start += CHUNK_SIZE;
+            }
+        }
+
+        for row in rows.slice(s![start.., ..]).rows() {
+            self.vectors.push(row.to_owned());
+        }
+        if self.chunking {
+            self.condense_vectors(CHUNK_SIZE);
+        }
+    }
+
     fn condense_vectors(&mut self, chunk_size: usize) {
         while self.vectors.len() >= chunk_size {
             let new_chunk = self.create_chunk(chunk_size, true);
-
-            self.chunks.push(new_chunk);
+            self.push_chunk(new_chunk);
         }
     }
 
+    fn push_chunk(&mut self, matrix: Matrix) {
+        let new_chunk = Chunk::narrow(matrix, self.precision);
+
+        // Norms come from the stored values, so they
+        // include any rounding from narrowing
+        let mut scratch = Matrix::zeros((0, 0));
+        let new_norms = new_chunk
+            .widen(&mut scratch)
+            .map_axis(Axis(1), |row| row.dot(&row));
+
+        self.chunks.push(new_chunk);
+        self.norms.push(new_norms);
+    }
+
     fn create_chunk(&mut self, chunk_size: usize, drain: bool) -> Matrix {
         let flat: Vec<f32> = if drain {
             self.vectors.drain(0..chunk_size).flatten().collect()
@@ -82,103 +263,676 @@ impl VectorTable {
         );
     }
 
+    // Largest k metrics first, for similarities
     pub fn top_k_by_metric(
         &self,
         distance: &dyn Distance,
-        // metric_fn: &dyn Fn(&dyn Distance, &Vector, &Vector) -> f32,
         vector: &Vector,
         k: usize,
     ) -> Vec<(Metric, usize)> {
-        let mut heap: MinMaxHeap<(Metric, usize)> = MinMaxHeap::with_capacity(k);
+        self.matrix_top_k_by_metric(distance, vector, k, false)
+    }
+
+    pub fn matrix_top_k_by_metric(
+        &self,
+        distance: &dyn Distance,
+        vector: &Vector,
+        k: usize,
+        asc: bool,
+    ) -> Vec<(Metric, usize)> {
+        let mut buffer = QueryBuffer::new();
+        self.matrix_top_k_by_metric_into(distance, vector, k, asc, &mut buffer);
+        buffer.results
+    }
+
+    // Leaves the top k results in `buffer.results`, best first. Reusing the
+    // same buffer across queries avoids any allocation once it has grown
+    pub fn matrix_top_k_by_metric_into(
+        &self,
+        distance: &dyn Distance,
+        vector: &Vector,
+        k: usize,
+        asc: bool,
+        buffer: &mut QueryBuffer,
+    ) {
+        let QueryBuffer {
+            scan,
+            heap,
+            results,
+        } = buffer;
+
+        heap.clear();
+        results.clear();
+
+        self.scan(distance, vector, scan, |metric, pos| {
+            push_bounded(heap, k, asc, (metric, pos))
+        });
+
+        if asc {
+            results.extend(heap.drain_asc());
+        } else {
+            results.extend(heap.drain_desc());
+        }
+    }
+
+    // Top k rows by the aggregated metric to several query vectors. Each chunk
+    // is widened once and scored against every query before moving on
+    pub fn multi_top_k_by_metric(
+        &self,
+        distance: &dyn Distance,
+        vectors: &[Vector],
+        k: usize,
+        asc: bool,
+        aggregation: Aggregation,
+    ) -> Vec<(Metric, usize)> {
+        assert!(!vectors.is_empty(), "Need at least one query vector");
+
+        let mut heap = MinMaxHeap::with_capacity(k);
+        let block_size = CHUNK_SIZE.max(self.vectors.len());
+        let mut dists = vec![0.; block_size];
+        let mut aggregated = vec![0.; block_size];
+        let mut widened = Matrix::zeros((0, 0));
+
+        for (chunk_index, (chunk, norms)) in zip(&self.chunks, &self.norms).enumerate() {
+            let chunk = chunk.widen(&mut widened);
+            let rows = chunk.nrows();
+            let metrics = &mut aggregated[..rows];
+            aggregate_block(
+                distance,
+                vectors,
+                aggregation,
+                chunk,
+                norms,
+                &mut dists,
+                metrics,
+            );
+
+            let chunk_pos = chunk_index * CHUNK_SIZE;
+            for (offset, metric) in metrics.iter().enumerate() {
+                push_bounded(
+                    &mut heap,
+                    k,
+                    asc,
+                    (OrderedFloat(*metric), chunk_pos + offset),
+                );
+            }
+        }
+
+        // The tail is stacked into one matrix and scored like a last chunk
+        if !self.vectors.is_empty() {
+            let mut tail = Matrix::zeros((0, 0));
+            let mut norms = Vector::zeros(0);
+            self.stack_tail(&mut tail, &mut norms);
+            let metrics = &mut aggregated[..self.vectors.len()];
+            aggregate_block(
+                distance,
+                vectors,
+                aggregation,
+                &tail,
+                &norms,
+                &mut dists,
+                metrics,
+            );
+
+            let tail_pos = self.chunks.len() * CHUNK_SIZE;
+            for (offset, metric) in metrics.iter().enumerate() {
+                push_bounded(
+                    &mut heap,
+                    k,
+                    asc,
+                    (OrderedFloat(*metric), tail_pos + offset),
+                );
+            }
+        }
+
+        if asc {
+            heap.into_vec_asc()
+        } else {
+            heap.into_vec_desc()
+        }
+    }
+
+    // Every row within `radius` of the vector, best first. When `asc` is false
+    // the metric is a similarity and rows scoring at least `radius` match
+    // instead. A limit keeps only the best matches, for dense neighborhoods
+    pub fn range_by_metric(
+        &self,
+        distance: &dyn Distance,
+        vector: &Vector,
+        radius: f32,
+        asc: bool,
+        filter: Option<&Filter>,
+        limit: Option<usize>,
+    ) -> Vec<(Metric, usize)> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut heap = MinMaxHeap::new();
+        let mut buffer = ScanBuffer::default();
 
-        for (pos, index_vector) in self.vectors.iter().enumerate() {
-            let result = distance.vector_dist(&vector, index_vector);
-            let element = (OrderedFloat(result), pos);
-            if heap.len() >= k {
-                heap.push_pop_min(element);
+        self.scan(distance, vector, &mut buffer, |metric, pos| {
+            let within = if asc {
+                metric.0 <= radius
             } else {
-                heap.push(element);
+                metric.0 >= radius
+            };
+            if within && filter.is_none_or(|filter| filter.contains(pos as u32)) {
+                push_bounded(&mut heap, limit, asc, (metric, pos));
             }
+        });
+
+        if asc {
+            heap.into_vec_asc()
+        } else {
+            heap.into_vec_desc()
         }
-        heap.into_vec_desc()
     }
 
-    pub fn matrix_top_k_by_metric(
-        &mut self,
+    // Fetches the best `fetch_k` rows, then picks k of them by maximal
+    // marginal relevance, using the same distance between the rows
+    pub fn mmr_top_k_by_metric(
+        &self,
         distance: &dyn Distance,
-        // metric_fn: &dyn Fn(&Vector, &Matrix) -> Vector,
         vector: &Vector,
         k: usize,
+        fetch_k: usize,
+        lambda: f32,
         asc: bool,
     ) -> Vec<(Metric, usize)> {
-        let mut heap: MinMaxHeap<(Metric, usize)> = MinMaxHeap::with_capacity(k);
-        let push_pop_fn = if asc {
-            MinMaxHeap::<(Metric, usize)>::push_pop_max
-        } else {
-            MinMaxHeap::<(Metric, usize)>::push_pop_min
-        };
+        let mut buffer = QueryBuffer::new();
+        self.matrix_top_k_by_metric_into(distance, vector, fetch_k.max(k), asc, &mut buffer);
+        let candidates = buffer.results;
+
+        let mut pool = Matrix::zeros((candidates.len(), self.dim));
+        for (mut row, (_, pos)) in pool.rows_mut().into_iter().zip(&candidates) {
+            row.assign(&self.get(*pos).unwrap());
+        }
+
+        select_mmr(&candidates, k, lambda, asc, |i| {
+            distance
+                .matrix_dist(&pool.row(i).to_owned(), &pool)
+                .to_vec()
+        })
+    }
 
-        // This is fine as long as the number of unchunked vectors is small,
-        // but it would be even better to delegate to the other implementation here.
-        // For that we'd need references to both distance functions though
-        let num_vectors = self.vectors.len();
-        let vector_chunk = self.create_chunk(num_vectors, false);
+    // Top k rows in the filter (or all rows), with some randomness mixed in
+    // as described by `exploration`. The same seed gives the same results
+    #[allow(clippy::too_many_arguments)]
+    pub fn explore_top_k_by_metric(
+        &self,
+        distance: &dyn Distance,
+        vector: &Vector,
+        k: usize,
+        asc: bool,
+        filter: Option<&Filter>,
+        exploration: Exploration,
+        seed: u64,
+    ) -> Vec<(Metric, usize)> {
+        let mut rng = StdRng::seed_from_u64(seed);
 
-        let mut chunks: Vec<&Matrix> = Vec::new();
-        for reference in self.chunks.iter() {
-            chunks.push(reference);
+        match exploration {
+            Exploration::Softmax { pool, temperature } => {
+                let candidates =
+                    self.top_k_after_by_metric(distance, vector, pool.max(k), asc, filter, None);
+                sample_softmax(&candidates, k, temperature, asc, &mut rng)
+            }
+            Exploration::EpsilonGreedy { epsilon } => {
+                let greedy = self.top_k_after_by_metric(distance, vector, k, asc, filter, None);
+
+                // Ids past the end of the table can't be drawn
+                let filter =
+                    filter.map(|filter| filter.and(&Filter::from_range(0..self.len() as u32)));
+                let population = filter
+                    .as_ref()
+                    .map_or(self.len() as u64, |filter| filter.len());
+
+                let mut greedy = greedy.into_iter();
+                let mut results: Vec<(Metric, usize)> = Vec::with_capacity(k);
+                while results.len() < k.min(population as usize) {
+                    let explore = rng.gen::<f32>() < epsilon;
+
+                    // Random rows that were already returned are
+                    // redrawn a few times before falling back to greedy
+                    let mut random = None;
+                    for _ in 0..8 {
+                        if !explore {
+                            break;
+                        }
+                        let n = rng.gen_range(0..population);
+                        let pos = match &filter {
+                            Some(filter) => filter.select(n as u32).unwrap() as usize,
+                            None => n as usize,
+                        };
+                        if !results.iter().any(|result| result.1 == pos) {
+                            random = Some(pos);
+                            break;
+                        }
+                    }
+
+                    let next = match random {
+                        Some(pos) => {
+                            let row = self.get(pos).unwrap().insert_axis(Axis(0));
+                            (OrderedFloat(distance.matrix_dist(vector, &row)[0]), pos)
+                        }
+                        _ => match greedy.find(|g| !results.iter().any(|r| r.1 == g.1)) {
+                            Some(next) => next,
+                            None => break,
+                        },
+                    };
+                    results.push(next);
+                }
+                results
+            }
         }
+    }
 
-        if self.vectors.len() > 0 {
-            chunks.push(&vector_chunk);
+    // Top k rows in the filter (or all rows) that come after the cursor in
+    // result order, best first. Ties are broken by position, so passing the
+    // last result of one page as the cursor gives exactly the next page
+    #[allow(clippy::too_many_arguments)]
+    pub fn top_k_after_by_metric(
+        &self,
+        distance: &dyn Distance,
+        vector: &Vector,
+        k: usize,
+        asc: bool,
+        filter: Option<&Filter>,
+        after: Option<(Metric, usize)>,
+    ) -> Vec<(Metric, usize)> {
+        let mut heap = MinMaxHeap::with_capacity(k);
+        let mut buffer = ScanBuffer::default();
+
+        self.scan(distance, vector, &mut buffer, |metric, pos| {
+            let element = (metric, pos);
+            let later = match after {
+                Some(cursor) if asc => element > cursor,
+                Some(cursor) => element < cursor,
+                None => true,
+            };
+            if later && filter.is_none_or(|filter| filter.contains(pos as u32)) {
+                push_bounded(&mut heap, k, asc, element);
+            }
+        });
+
+        if asc {
+            heap.into_vec_asc()
+        } else {
+            heap.into_vec_desc()
         }
+    }
 
-        // Iterate through the chunks computing distances and inserting into the heap
-        for (chunk_index, chunk) in chunks.iter().enumerate() {
-            let results: Vector = distance.matrix_dist(&vector, &chunk);
+    // Top k rows in the filter, found by whichever plan the planner picks
+    // for the filter's selectivity. Every plan finds the same rows
+    pub fn planned_top_k_by_metric(
+        &self,
+        distance: &dyn Distance,
+        vector: &Vector,
+        k: usize,
+        asc: bool,
+        filter: &Filter,
+        planner: &QueryPlanner,
+    ) -> (Vec<(Metric, usize)>, QueryStats) {
+        let mut stats = planner.plan(filter, self.len());
 
-            let chunk_pos = chunk_index * CHUNK_SIZE;
-            let result_positions = Array::from_iter(0..chunk.shape()[0]) + chunk_pos as usize;
-            let result_pairs = zip(results, result_positions);
+        let results = match stats.plan {
+            QueryPlan::MatchingRows => {
+                let positions: Vec<usize> = filter
+                    .iter()
+                    .map(|pos| pos as usize)
+                    .take_while(|pos| *pos < self.len())
+                    .collect();
+                let mut rows = Matrix::zeros((positions.len(), self.dim));
+                let mut norms = Vector::zeros(positions.len());
+                let chunked = self.chunks.len() * CHUNK_SIZE;
+                for (index, pos) in positions.iter().enumerate() {
+                    let row = self.get(*pos).unwrap();
+                    norms[index] = if *pos < chunked {
+                        self.norms[pos / CHUNK_SIZE][pos % CHUNK_SIZE]
+                    } else {
+                        row.dot(&row)
+                    };
+                    rows.row_mut(index).assign(&row);
+                }
+
+                let mut dists = vec![0.; positions.len()];
+                distance.matrix_dist_with_norms_into(vector, &rows, &norms, &mut dists);
+                stats.rows_scored = positions.len();
 
-            for (result, pos) in result_pairs {
-                let element = (OrderedFloat(result), pos);
-                if heap.len() >= k {
-                    push_pop_fn(&mut heap, element);
+                let mut heap = MinMaxHeap::with_capacity(k);
+                for (result, pos) in zip(dists, positions) {
+                    push_bounded(&mut heap, k, asc, (OrderedFloat(result), pos));
+                }
+                if asc {
+                    heap.into_vec_asc()
                 } else {
-                    heap.push(element);
+                    heap.into_vec_desc()
                 }
             }
+            QueryPlan::FilteredScan => {
+                stats.rows_scored = self.len();
+                self.top_k_after_by_metric(distance, vector, k, asc, Some(filter), None)
+            }
+            QueryPlan::OverFetch => {
+                let fetch_k = planner.overfetch_k(k, stats.selectivity);
+                stats.rows_scored = self.len();
+
+                let mut results: Vec<(Metric, usize)> = self
+                    .top_k_after_by_metric(distance, vector, fetch_k, asc, None, None)
+                    .into_iter()
+                    .filter(|(_, pos)| filter.contains(*pos as u32))
+                    .collect();
+
+                // Too few passed the filter, but some further rows might
+                if results.len() < k && fetch_k < self.len() {
+                    stats.fell_back = true;
+                    stats.rows_scored += self.len();
+                    results =
+                        self.top_k_after_by_metric(distance, vector, k, asc, Some(filter), None);
+                }
+                results.truncate(k);
+                results
+            }
+        };
+        (results, stats)
+    }
+
+    // Iterates over pages of results in order, see `ResultPages`
+    pub fn pages_by_metric<'a>(
+        &'a self,
+        distance: &'a dyn Distance,
+        vector: &Vector,
+        page_size: usize,
+        asc: bool,
+        filter: Option<&'a Filter>,
+    ) -> ResultPages<'a> {
+        ResultPages {
+            table: self,
+            distance,
+            vector: vector.clone(),
+            page_size,
+            asc,
+            filter,
+            cursor: None,
+            candidates: VecDeque::new(),
+            exhausted: false,
         }
+    }
+
+    // Top k rows in the filter, best first, along with how many rows in the
+    // filter have each value of the attribute. With a radius, the counts only
+    // include rows within it (or scoring at least it, for similarities)
+    #[allow(clippy::too_many_arguments)]
+    pub fn faceted_top_k_by_metric(
+        &self,
+        distance: &dyn Distance,
+        vector: &Vector,
+        k: usize,
+        asc: bool,
+        filter: Option<&Filter>,
+        radius: Option<f32>,
+        attribute: &Attribute,
+    ) -> (Vec<(Metric, usize)>, BTreeMap<String, u64>) {
+        let mut heap = MinMaxHeap::with_capacity(k);
+        let mut within = Filter::new();
+        let mut buffer = ScanBuffer::default();
 
-        heap.into_vec_desc()
+        self.scan(distance, vector, &mut buffer, |metric, pos| {
+            if filter.is_some_and(|filter| !filter.contains(pos as u32)) {
+                return;
+            }
+            push_bounded(&mut heap, k, asc, (metric, pos));
+
+            if let Some(radius) = radius {
+                if (asc && metric.0 <= radius) || (!asc && metric.0 >= radius) {
+                    within.insert(pos as u32);
+                }
+            }
+        });
+
+        // Rows within the radius were already restricted to the filter
+        let counts = match (radius, filter) {
+            (Some(_), _) => attribute.facet_counts(Some(&within)),
+            (None, filter) => attribute.facet_counts(filter),
+        };
+
+        let results = if asc {
+            heap.into_vec_asc()
+        } else {
+            heap.into_vec_desc()
+        };
+        (results, counts)
     }
 
+    // The top k rows for each value of a categorical attribute, found in a
+    // single scan with one heap per value. Rows without a value are skipped
+    pub fn grouped_top_k_by_metric(
+        &self,
+        distance: &dyn Distance,
+        vector: &Vector,
+        k: usize,
+        asc: bool,
+        attribute: &Attribute,
+    ) -> HashMap<String, Vec<(Metric, usize)>> {
+        // Rows are scanned in order, so the memberships (sorted by row) let
+        // the scan find each row's values by walking forward through them
+        let memberships = attribute.memberships();
+
+        let mut heaps: HashMap<u32, MinMaxHeap<(Metric, usize)>> = HashMap::new();
+        let mut cursor = 0;
+        let mut buffer = ScanBuffer::default();
+
+        self.scan(distance, vector, &mut buffer, |metric, pos| {
+            while cursor < memberships.len() && (memberships[cursor].0 as usize) < pos {
+                cursor += 1;
+            }
+            while cursor < memberships.len() && memberships[cursor].0 as usize == pos {
+                let heap = heaps.entry(memberships[cursor].1).or_default();
+                push_bounded(heap, k, asc, (metric, pos));
+                cursor += 1;
+            }
+        });
+
+        heaps
+            .into_iter()
+            .map(|(code, heap)| {
+                let value = attribute.value(code).unwrap_or_default().to_string();
+                let results = if asc {
+                    heap.into_vec_asc()
+                } else {
+                    heap.into_vec_desc()
+                };
+                (value, results)
+            })
+            .collect()
+    }
+
+    // Computes the metric between the vector and every row, chunks
+    // first and then the tail, and passes each to `visit` with its position
+    pub(crate) fn scan<F: FnMut(Metric, usize)>(
+        &self,
+        distance: &dyn Distance,
+        vector: &Vector,
+        buffer: &mut ScanBuffer,
+        mut visit: F,
+    ) {
+        let ScanBuffer {
+            dists,
+            widened,
+            tail,
+            norms: tail_norms,
+        } = buffer;
+        dists.resize(CHUNK_SIZE.max(self.vectors.len()), 0.);
+
+        // Iterate through the chunks computing distances
+        for (chunk_index, (chunk, norms)) in zip(&self.chunks, &self.norms).enumerate() {
+            let chunk = chunk.widen(widened);
+            let results = &mut dists[..chunk.nrows()];
+            distance.matrix_dist_with_norms_into(vector, chunk, norms, results);
+
+            let chunk_pos = chunk_index * CHUNK_SIZE;
+            for (offset, result) in results.iter().enumerate() {
+                visit(OrderedFloat(*result), chunk_pos + offset);
+            }
+        }
+
+        // Vectors that haven't filled up a chunk yet are stacked into one
+        // matrix, so distances always see a matrix of rows
+        if !self.vectors.is_empty() {
+            self.stack_tail(tail, tail_norms);
+            let results = &mut dists[..self.vectors.len()];
+            distance.matrix_dist_with_norms_into(vector, tail, tail_norms, results);
+
+            let tail_pos = self.chunks.len() * CHUNK_SIZE;
+            for (offset, result) in results.iter().enumerate() {
+                visit(OrderedFloat(*result), tail_pos + offset);
+            }
+        }
+    }
+
+    // Copies the rows that haven't filled a chunk yet into `tail`, and their
+    // squared L2 norms into `norms`, reusing both when the shapes match
+    fn stack_tail(&self, tail: &mut Matrix, norms: &mut Vector) {
+        let shape = (self.vectors.len(), self.dim);
+        if tail.dim() != shape {
+            *tail = Matrix::zeros(shape);
+        }
+        if norms.len() != shape.0 {
+            *norms = Vector::zeros(shape.0);
+        }
+
+        for ((mut row, norm), vector) in zip(zip(tail.rows_mut(), norms.iter_mut()), &self.vectors)
+        {
+            row.assign(vector);
+            *norm = vector.dot(vector);
+        }
+    }
+
+    // Smallest k metrics first, for distances
     pub fn bottom_k_by_metric(
         &self,
         distance: &dyn Distance,
-        // metric_fn: &dyn Fn(&Vector, &Vector) -> f32,
         vector: &Vector,
         k: usize,
     ) -> Vec<(Metric, usize)> {
-        let mut heap: BinaryHeap<(Reverse<Metric>, usize)> = BinaryHeap::with_capacity(k);
+        self.matrix_top_k_by_metric(distance, vector, k, true)
+    }
+}
+
+// Pages through query results in order. Each scan over-fetches
+// `PAGES_PER_SCAN` pages of candidates past the last one fetched, and pages
+// are served from those until they run out, so deep paging costs one scan
+// per `PAGES_PER_SCAN` pages. The cursor can also be saved and passed to
+// `top_k_after_by_metric` later, to resume paging from another request
+pub struct ResultPages<'a> {
+    table: &'a VectorTable,
+    distance: &'a dyn Distance,
+    vector: Vector,
+    page_size: usize,
+    asc: bool,
+    filter: Option<&'a Filter>,
+    cursor: Option<(Metric, usize)>,
+    candidates: VecDeque<(Metric, usize)>,
+    exhausted: bool,
+}
+
+const PAGES_PER_SCAN: usize = 8;
 
-        for (pos, index_vector) in self.vectors.iter().enumerate() {
-            let result = distance.vector_dist(&vector, index_vector);
-            let element = (Reverse(OrderedFloat(result)), pos);
-            heap.push(element);
+impl<'a> ResultPages<'a> {
+    // Last result returned so far
+    pub fn cursor(&self) -> Option<(Metric, usize)> {
+        self.cursor
+    }
+
+    // Fetches the next candidates past the last one already held
+    fn refill(&mut self) {
+        let fetch_k = self.page_size * PAGES_PER_SCAN;
+        let after = self.candidates.back().copied().or(self.cursor);
+        let fetched = self.table.top_k_after_by_metric(
+            self.distance,
+            &self.vector,
+            fetch_k,
+            self.asc,
+            self.filter,
+            after,
+        );
+
+        self.exhausted = fetched.len() < fetch_k;
+        self.candidates.extend(fetched);
+    }
+}
+
+impl<'a> Iterator for ResultPages<'a> {
+    type Item = Vec<(Metric, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.candidates.len() < self.page_size && !self.exhausted {
+            self.refill();
         }
-        heap.into_sorted_vec()
-            .iter()
-            .map(|r| (r.0 .0, r.1))
-            .collect()
+
+        let len = self.page_size.min(self.candidates.len());
+        let page: Vec<(Metric, usize)> = self.candidates.drain(..len).collect();
+
+        self.cursor = Some(*page.last()?);
+        Some(page)
+    }
+}
+
+// Aggregates the metric from every query vector to each row of a block
+// into `metrics`, using `dists` as scratch for one query's distances
+fn aggregate_block(
+    distance: &dyn Distance,
+    vectors: &[Vector],
+    aggregation: Aggregation,
+    block: &Matrix,
+    norms: &Vector,
+    dists: &mut [f32],
+    metrics: &mut [f32],
+) {
+    let dists = &mut dists[..block.nrows()];
+    for (query, vector) in vectors.iter().enumerate() {
+        distance.matrix_dist_with_norms_into(vector, block, norms, dists);
+
+        if query == 0 {
+            metrics.copy_from_slice(dists);
+        } else {
+            for (acc, dist) in zip(metrics.iter_mut(), dists.iter()) {
+                *acc = aggregation.combine(*acc, *dist);
+            }
+        }
+    }
+    for acc in metrics.iter_mut() {
+        *acc = aggregation.finish(*acc, vectors.len());
+    }
+}
+
+// Pushes onto a heap holding at most the best k elements
+pub(crate) fn push_bounded(
+    heap: &mut MinMaxHeap<(Metric, usize)>,
+    k: usize,
+    asc: bool,
+    element: (Metric, usize),
+) {
+    if heap.len() < k {
+        heap.push(element);
+    } else if asc {
+        heap.push_pop_max(element);
+    } else {
+        heap.push_pop_min(element);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Vector, VectorTable};
+    use super::{Matrix, Metric, Precision, QueryBuffer, Vector, VectorTable, CHUNK_SIZE};
+    use crate::distances::angular::InnerProduct;
+    use crate::distances::hyperbolic::HalfPlane;
+    use crate::distances::lp_norm::L2;
+    use crate::distances::Distance;
+    use crate::primitives::aggregation::Aggregation;
+    use crate::primitives::attributes::Attribute;
+    use crate::primitives::exploration::Exploration;
+    use crate::primitives::filter::Filter;
+    use crate::primitives::planner::{QueryPlan, QueryPlanner};
     use crate::primitives::vector::random_vector;
+    use ordered_float::OrderedFloat;
 
     #[test]
     fn insert_many() {
@@ -197,11 +951,565 @@ mod tests {
 
     #[test]
     fn top_k_by_metric() {
-        // TODO: Test with inner product
+        let dim = 16;
+        let k = 10;
+
+        let mut vectors: Vec<Vector> = (0..CHUNK_SIZE + 100).map(|_| random_vector(dim)).collect();
+        let query = random_vector(dim);
+        // Row 42 lives in a full chunk and beats every other row
+        vectors[42] = &query * 10.;
+
+        for precision in [Precision::F32, Precision::F16] {
+            let mut table = VectorTable::with_precision(dim, precision);
+            table.insert_many(&vectors);
+
+            let results = table.top_k_by_metric(&InnerProduct {}, &query, k);
+            assert_eq!(results.len(), k);
+            assert_eq!(results[0].1, 42);
+            assert!(results.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+        }
     }
 
     #[test]
     fn bottom_k_by_metric() {
-        // TODO: Test with l2 distance
+        let dim = 16;
+        let k = 10;
+
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE + 100).map(|_| random_vector(dim)).collect();
+
+        for precision in [Precision::F32, Precision::F16] {
+            let mut table = VectorTable::with_precision(dim, precision);
+            table.insert_many(&vectors);
+
+            // Row 42 lives in a full chunk
+            let results = table.bottom_k_by_metric(&L2 {}, &vectors[42], k);
+            assert_eq!(results.len(), k);
+            assert_eq!(results[0].1, 42);
+            assert!(results.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        }
+    }
+
+    #[test]
+    fn matrix_top_k_by_metric_into() {
+        let dim = 32;
+        let k = 10;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE * 2 + 100)
+            .map(|_| random_vector(dim))
+            .collect();
+
+        let mut table = VectorTable::new(dim, true);
+        table.insert_many(&vectors);
+
+        let distances: [(&dyn Distance, bool); 3] = [
+            (&InnerProduct {}, false),
+            (&L2 {}, true),
+            (&HalfPlane {}, true),
+        ];
+
+        let mut buffer = QueryBuffer::new();
+        for (distance, asc) in distances {
+            let query = random_vector(dim);
+
+            let mut expected: Vec<(f32, usize)> = vectors
+                .iter()
+                .enumerate()
+                .map(|(pos, v)| (distance.vector_dist(&query, v), pos))
+                .collect();
+            expected.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            if !asc {
+                expected.reverse();
+            }
+
+            table.matrix_top_k_by_metric_into(distance, &query, k, asc, &mut buffer);
+
+            assert_eq!(buffer.results.len(), k);
+            for (result, expected) in buffer.results.iter().zip(expected.iter()) {
+                assert!((result.0 .0 - expected.0).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn matrix_top_k_by_metric_into_reuses_scratch() {
+        let dim = 32;
+        let k = 10;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE * 2 + 100)
+            .map(|_| random_vector(dim))
+            .collect();
+
+        let mut table = VectorTable::with_precision(dim, Precision::F16);
+        table.insert_many(&vectors);
+
+        // Half-precision chunks and the tail are widened into separate
+        // scratch matrices, so neither moves once the buffer has warmed up
+        let mut buffer = QueryBuffer::new();
+        table.matrix_top_k_by_metric_into(&L2 {}, &vectors[0], k, true, &mut buffer);
+        let widened = buffer.scan.widened.as_ptr();
+        let tail = buffer.scan.tail.as_ptr();
+
+        table.matrix_top_k_by_metric_into(&L2 {}, &vectors[1], k, true, &mut buffer);
+        assert_eq!(buffer.scan.widened.as_ptr(), widened);
+        assert_eq!(buffer.scan.tail.as_ptr(), tail);
+    }
+
+    #[test]
+    fn range_by_metric() {
+        let dim = 16;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE + 100).map(|_| random_vector(dim)).collect();
+        let query = random_vector(dim);
+
+        let mut table = VectorTable::new(dim, true);
+        table.insert_many(&vectors);
+
+        let mut dists: Vec<(f32, usize)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(pos, v)| (L2 {}.vector_dist(&query, v), pos))
+            .collect();
+        dists.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let radius = (dists[19].0 + dists[20].0) / 2.;
+
+        let results = table.range_by_metric(&L2 {}, &query, radius, true, None, None);
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        let expected: Vec<usize> = dists[..20].iter().map(|d| d.1).collect();
+        assert_eq!(positions, expected);
+
+        let mut filter = Filter::new();
+        filter.insert_many(&[expected[3] as u32, expected[7] as u32, dists[50].1 as u32]);
+        let results = table.range_by_metric(&L2 {}, &query, radius, true, Some(&filter), None);
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        assert_eq!(positions, [expected[3], expected[7]]);
+
+        let results = table.range_by_metric(&L2 {}, &query, radius, true, None, Some(5));
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        assert_eq!(positions, expected[..5]);
+    }
+
+    #[test]
+    fn grouped_top_k_by_metric() {
+        let dim = 16;
+        let k = 5;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE + 100).map(|_| random_vector(dim)).collect();
+        let query = random_vector(dim);
+
+        let mut table = VectorTable::new(dim, true);
+        table.insert_many(&vectors);
+
+        // Every third row is left without a genre
+        let genres = ["jazz", "folk"];
+        let mut attribute = Attribute::new();
+        for pos in 0..vectors.len() {
+            if pos % 3 != 0 {
+                attribute.insert(genres[pos % 2], pos as u32);
+            }
+        }
+
+        let groups = table.grouped_top_k_by_metric(&InnerProduct {}, &query, k, false, &attribute);
+        assert_eq!(groups.len(), 2);
+
+        for (genre, results) in groups {
+            let filter = attribute.filter(&genre).unwrap();
+            let mut expected: Vec<(f32, usize)> = vectors
+                .iter()
+                .enumerate()
+                .filter(|(pos, _)| filter.contains(*pos as u32))
+                .map(|(pos, v)| (query.dot(v), pos))
+                .collect();
+            expected.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+            let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+            let expected: Vec<usize> = expected[..k].iter().map(|e| e.1).collect();
+            assert_eq!(positions, expected);
+        }
+    }
+
+    #[test]
+    fn faceted_top_k_by_metric() {
+        let dim = 16;
+        let k = 5;
+        let vectors: Vec<Vector> = (0..1000).map(|_| random_vector(dim)).collect();
+        let query = random_vector(dim);
+
+        let mut table = VectorTable::new(dim, false);
+        table.insert_many(&vectors);
+
+        let genres = ["jazz", "folk", "soul"];
+        let mut attribute = Attribute::new();
+        for pos in 0..vectors.len() {
+            attribute.insert(genres[pos % 3], pos as u32);
+        }
+
+        let mut filter = Filter::new();
+        filter.insert_many(&(0..500).collect::<Vec<u32>>());
+
+        let (results, counts) =
+            table.faceted_top_k_by_metric(&L2 {}, &query, k, true, Some(&filter), None, &attribute);
+        assert_eq!(results.len(), k);
+        assert!(results.iter().all(|r| r.1 < 500));
+        assert_eq!(counts["jazz"], 167);
+        assert_eq!(counts["folk"], 167);
+        assert_eq!(counts["soul"], 166);
+
+        let radius = results[k - 1].0 .0;
+        let (_, counts) = table.faceted_top_k_by_metric(
+            &L2 {},
+            &query,
+            k,
+            true,
+            Some(&filter),
+            Some(radius),
+            &attribute,
+        );
+        assert_eq!(counts.values().sum::<u64>(), k as u64);
+    }
+
+    #[test]
+    fn get() {
+        let dim = 8;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE + 10).map(|_| random_vector(dim)).collect();
+
+        let mut table = VectorTable::with_precision(dim, Precision::BF16);
+        table.insert_many(&vectors);
+
+        let widened = table.get(3).unwrap();
+        assert!((&widened - &vectors[3]).iter().all(|d| d.abs() < 0.01));
+        assert_eq!(
+            table.get(CHUNK_SIZE + 4),
+            Some(vectors[CHUNK_SIZE + 4].clone())
+        );
+        assert_eq!(table.get(CHUNK_SIZE + 10), None);
+    }
+
+    #[test]
+    fn mmr_top_k_by_metric() {
+        let dim = 16;
+        let k = 5;
+        let base: Vec<Vector> = (0..100).map(|_| random_vector(dim)).collect();
+
+        // Every vector shows up twice
+        let mut table = VectorTable::new(dim, false);
+        table.insert_many(&base);
+        table.insert_many(&base);
+
+        let query = random_vector(dim);
+        let plain = table.mmr_top_k_by_metric(&L2 {}, &query, k, 20, 1., true);
+        let diverse = table.mmr_top_k_by_metric(&L2 {}, &query, k, 20, 0.5, true);
+
+        let duplicates = |results: &[(Metric, usize)]| {
+            let mut rows: Vec<usize> = results.iter().map(|r| r.1 % 100).collect();
+            rows.sort();
+            rows.dedup();
+            k - rows.len()
+        };
+
+        assert_eq!(plain.len(), k);
+        assert!(duplicates(&plain) > 0);
+        assert_eq!(duplicates(&diverse), 0);
+    }
+
+    #[test]
+    fn explore_top_k_by_metric() {
+        let dim = 16;
+        let k = 10;
+        let vectors: Vec<Vector> = (0..1000).map(|_| random_vector(dim)).collect();
+        let query = random_vector(dim);
+
+        let mut table = VectorTable::new(dim, false);
+        table.insert_many(&vectors);
+
+        let mut filter = Filter::new();
+        filter.insert_many(&(0..1000).step_by(2).collect::<Vec<u32>>());
+
+        let greedy = table.matrix_top_k_by_metric(&L2 {}, &query, 1000, true);
+        let greedy: Vec<usize> = greedy.iter().map(|r| r.1).filter(|p| p % 2 == 0).collect();
+
+        for exploration in [
+            Exploration::Softmax {
+                pool: 50,
+                temperature: 0.1,
+            },
+            Exploration::EpsilonGreedy { epsilon: 0.3 },
+        ] {
+            let explore = |seed| {
+                table.explore_top_k_by_metric(
+                    &L2 {},
+                    &query,
+                    k,
+                    true,
+                    Some(&filter),
+                    exploration,
+                    seed,
+                )
+            };
+            let results = explore(11);
+            let mut positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+
+            assert_eq!(results, explore(11));
+            assert_ne!(positions, greedy[..k]);
+
+            positions.sort();
+            positions.dedup();
+            assert_eq!(positions.len(), k);
+            assert!(positions.iter().all(|p| p % 2 == 0));
+        }
+
+        // Without any exploration the greedy results come back
+        let results = table.explore_top_k_by_metric(
+            &L2 {},
+            &query,
+            k,
+            true,
+            Some(&filter),
+            Exploration::EpsilonGreedy { epsilon: 0. },
+            11,
+        );
+        let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+        assert_eq!(positions, greedy[..k]);
+
+        // Ids past the end of the table are never drawn
+        let mut filter = Filter::from_range(990..5000);
+        filter.insert(100_000);
+        let results = table.explore_top_k_by_metric(
+            &L2 {},
+            &query,
+            20,
+            true,
+            Some(&filter),
+            Exploration::EpsilonGreedy { epsilon: 1. },
+            11,
+        );
+        assert_eq!(results.len(), 10);
+        assert!(results.iter().all(|r| (990..1000).contains(&r.1)));
+    }
+
+    #[test]
+    fn pages_by_metric() {
+        let dim = 16;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE + 100).map(|_| random_vector(dim)).collect();
+        let query = random_vector(dim);
+
+        let mut table = VectorTable::new(dim, true);
+        table.insert_many(&vectors);
+
+        // Duplicates make sure ties are split across pages correctly
+        table.insert_many(&vectors[..50]);
+
+        let mut filter = Filter::new();
+        filter.insert_many(&(0..table.len() as u32).step_by(3).collect::<Vec<u32>>());
+
+        for (distance, asc) in [(&InnerProduct {} as &dyn Distance, false), (&L2 {}, true)] {
+            let expected =
+                table.top_k_after_by_metric(distance, &query, 200, asc, Some(&filter), None);
+
+            let pages: Vec<_> = table
+                .pages_by_metric(distance, &query, 20, asc, Some(&filter))
+                .take(10)
+                .collect();
+
+            assert!(pages.iter().all(|page| page.len() == 20));
+            assert_eq!(pages.concat(), expected);
+
+            // Pages that straddle a refill of the candidates
+            let pages: Vec<_> = table
+                .pages_by_metric(distance, &query, 7, asc, Some(&filter))
+                .take(28)
+                .collect();
+            assert_eq!(pages.concat(), expected[..196]);
+
+            // Resuming from a saved cursor
+            let next = table.top_k_after_by_metric(
+                distance,
+                &query,
+                20,
+                asc,
+                Some(&filter),
+                Some(expected[99]),
+            );
+            assert_eq!(next, expected[100..120]);
+        }
+
+        let count: usize = table
+            .pages_by_metric(&L2 {}, &query, 1000, true, Some(&filter))
+            .map(|page| page.len())
+            .sum();
+        assert_eq!(count as u64, filter.len());
+    }
+
+    #[test]
+    fn multi_top_k_by_metric() {
+        let dim = 16;
+        let k = 10;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE + 100).map(|_| random_vector(dim)).collect();
+        let queries: Vec<Vector> = (0..3).map(|_| random_vector(dim)).collect();
+
+        let mut table = VectorTable::new(dim, true);
+        table.insert_many(&vectors);
+
+        for aggregation in [
+            Aggregation::Sum,
+            Aggregation::Mean,
+            Aggregation::Max,
+            Aggregation::Min,
+        ] {
+            let mut expected: Vec<(f32, usize)> = vectors
+                .iter()
+                .enumerate()
+                .map(|(pos, v)| {
+                    let dists: Vec<f32> = queries.iter().map(|q| L2 {}.vector_dist(q, v)).collect();
+                    (aggregation.aggregate(&dists), pos)
+                })
+                .collect();
+            expected.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+            let results = table.multi_top_k_by_metric(&L2 {}, &queries, k, true, aggregation);
+
+            assert_eq!(results.len(), k);
+            for (result, expected) in results.iter().zip(expected.iter()) {
+                assert!((result.0 .0 - expected.0).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn half_precision_matches_f32() {
+        let dim = 32;
+        let k = 10;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE * 2 + 100)
+            .map(|_| random_vector(dim))
+            .collect();
+        let query = random_vector(dim);
+
+        let mut table = VectorTable::new(dim, true);
+        table.insert_many(&vectors);
+        let expected = table.matrix_top_k_by_metric(&L2 {}, &query, k, true);
+
+        for precision in [Precision::F16, Precision::BF16] {
+            let mut half_table = VectorTable::with_precision(dim, precision);
+            half_table.insert_many(&vectors);
+
+            let results = half_table.matrix_top_k_by_metric(&L2 {}, &query, k, true);
+
+            assert_eq!(results.len(), k);
+            assert!((results[0].0 .0 - expected[0].0 .0).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn insert_rows() {
+        let dim = 8;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE * 2 + 20)
+            .map(|_| random_vector(dim))
+            .collect();
+        let views: Vec<_> = vectors.iter().map(|v| v.view()).collect();
+        let rows = ndarray::stack(ndarray::Axis(0), &views).unwrap();
+
+        let mut expected = VectorTable::new(dim, true);
+        expected.insert_many(&vectors[..10]);
+        expected.insert_many(&vectors[10..]);
+
+        // Starts from a partially filled chunk
+        let mut table = VectorTable::new(dim, true);
+        table.insert_many(&vectors[..10]);
+        table.insert_rows(rows.slice(ndarray::s![10.., ..]));
+
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn planned_top_k_by_metric() {
+        let dim = 8;
+        let k = 10;
+        let distance = L2 {};
+
+        // Rows score differently depending on the batch they're scored in, so
+        // spread them along one axis, in shuffled order, far enough apart that
+        // rounding can't reorder them
+        let num_vectors = CHUNK_SIZE + 500;
+        let vectors: Vec<Vector> = (0..num_vectors)
+            .map(|pos| {
+                let mut vector = Vector::zeros(dim);
+                vector[0] = ((pos * 7919) % num_vectors) as f32 * 0.01;
+                vector
+            })
+            .collect();
+        let mut table = VectorTable::new(dim, true);
+        table.insert_many(&vectors);
+        let mut query = Vector::zeros(dim);
+        query[0] = -1.;
+
+        let planner = QueryPlanner::new();
+        let filters = [
+            (Filter::from_range(4000..4200), QueryPlan::MatchingRows),
+            (Filter::from_range(0..1000), QueryPlan::FilteredScan),
+            (Filter::from_range(500..4596), QueryPlan::OverFetch),
+        ];
+
+        for (filter, plan) in filters {
+            let (results, stats) =
+                table.planned_top_k_by_metric(&distance, &query, k, true, &filter, &planner);
+            assert_eq!(stats.plan, plan);
+
+            let expected =
+                table.top_k_after_by_metric(&distance, &query, k, true, Some(&filter), None);
+            let positions: Vec<usize> = results.iter().map(|r| r.1).collect();
+            let expected: Vec<usize> = expected.iter().map(|r| r.1).collect();
+            assert_eq!(positions, expected);
+        }
+    }
+
+    #[test]
+    fn range_exact_duplicate() {
+        let dim = 128;
+        let vectors: Vec<Vector> = (0..CHUNK_SIZE + 100).map(|_| random_vector(dim)).collect();
+        let mut table = VectorTable::new(dim, true);
+        table.insert_many(&vectors);
+
+        // Rows in a full chunk are scored with the cached norms
+        let distances: [&dyn Distance; 2] = [&L2 {}, &HalfPlane {}];
+        for distance in distances {
+            for (pos, vector) in vectors.iter().enumerate().take(20) {
+                let results = table.range_by_metric(distance, vector, 1e-4, true, None, None);
+                assert_eq!(results, [(OrderedFloat(0.), pos)]);
+            }
+        }
+    }
+
+    // Like a vectorized Python distance, which can only score matrices
+    struct MatrixOnly;
+
+    impl Distance for MatrixOnly {
+        fn vector_dist(&self, _a: &Vector, _b: &Vector) -> f32 {
+            panic!("Rows should be scored as a matrix")
+        }
+
+        fn matrix_dist(&self, a: &Vector, b: &Matrix) -> Vector {
+            L2 {}.matrix_dist(a, b)
+        }
+    }
+
+    #[test]
+    fn scan_tail_as_matrix() {
+        let dim = 8;
+        let k = 10;
+
+        for (chunking, num_vectors) in [(false, 100), (true, CHUNK_SIZE + 100)] {
+            let mut table = VectorTable::new(dim, chunking);
+            let vectors: Vec<Vector> = (0..num_vectors).map(|_| random_vector(dim)).collect();
+            table.insert_many(&vectors);
+
+            let query = vectors[num_vectors - 1].clone();
+            let results = table.matrix_top_k_by_metric(&MatrixOnly, &query, k, true);
+            assert_eq!(results.len(), k);
+            assert_eq!(results[0].1, num_vectors - 1);
+
+            let multi = table.multi_top_k_by_metric(
+                &MatrixOnly,
+                &[query.clone(), query],
+                k,
+                true,
+                Aggregation::Mean,
+            );
+            assert_eq!(multi[0].1, num_vectors - 1);
+        }
     }
 }
@@ -0,0 +1,328 @@
+extern crate numpy;
+extern crate pyo3;
+
+use crate::distances::Distance;
+use crate::indexes::flat::custom::IndexFlatCustom;
+use crate::indexes::flat::hp::IndexFlatHP;
+use crate::indexes::flat::ip::IndexFlatIP;
+use crate::indexes::flat::l2::IndexFlatL2;
+use crate::indexes::flat::FlatIndex;
+use crate::prelude::*;
+use crate::primitives::vector_table::Metric;
+
+use std::cell::RefCell;
+
+use ndarray::Axis;
+use numpy::{PyReadonlyArray1, PyReadonlyArray2, ToPyArray};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+type QueryResults = Vec<(f32, usize)>;
+
+fn to_vector(vector: PyReadonlyArray1<f32>) -> Vector {
+    vector.as_array().to_owned()
+}
+
+fn to_vectors(vectors: PyReadonlyArray2<f32>) -> Vec<Vector> {
+    vectors
+        .as_array()
+        .axis_iter(Axis(0))
+        .map(|row| row.to_owned())
+        .collect()
+}
+
+fn to_results(results: Vec<(Metric, usize)>) -> QueryResults {
+    results
+        .iter()
+        .map(|(metric, pos)| (metric.0, *pos))
+        .collect()
+}
+
+fn check_dim(vector: &Vector, dim: usize) -> PyResult<()> {
+    if vector.len() != dim {
+        return Err(PyValueError::new_err(format!(
+            "vector dim {} doesn't match index dim {}",
+            vector.len(),
+            dim
+        )));
+    }
+    Ok(())
+}
+
+// Docstring for every index's `query_mmr`, see `FlatIndex::query_mmr`
+macro_rules! query_mmr_doc {
+    () => {
+        "Diversified top k, picked from the best `fetch_k` results by maximal
+marginal relevance. Lower `lambda_mult` favors diversity over closeness"
+    };
+}
+
+/// Distance backed by a Python callable
+///
+/// The callable receives two 1-D float32 numpy arrays and returns a float,
+/// or, when `vectorized` is set, a 1-D query array and a 2-D array of rows
+/// and returns one float per row. Any callable works, including functions
+/// compiled with `numba.njit`.
+///
+/// Every evaluation crosses back into the interpreter while holding the GIL,
+/// so this is much slower than the native distances and meant for prototyping
+/// a metric before implementing it in Rust. Prefer `vectorized` callables,
+/// which are invoked once per chunk instead of once per row.
+pub struct PyDistance {
+    func: PyObject,
+    vectorized: bool,
+    // Distance methods can't fail, so the first Python exception is
+    // stashed here and raised once the query finishes
+    error: RefCell<Option<PyErr>>,
+}
+
+impl PyDistance {
+    pub fn new(func: PyObject, vectorized: bool) -> PyDistance {
+        PyDistance {
+            func,
+            vectorized,
+            error: RefCell::new(None),
+        }
+    }
+
+    fn record_error(&self, err: PyErr) {
+        let mut error = self.error.borrow_mut();
+        if error.is_none() {
+            *error = Some(err);
+        }
+    }
+
+    pub fn take_error(&self) -> PyResult<()> {
+        match self.error.borrow_mut().take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Distance for PyDistance {
+    fn vector_dist(&self, a: &Vector, b: &Vector) -> f32 {
+        Python::with_gil(|py| {
+            self.func
+                .call1(py, (a.to_pyarray(py), b.to_pyarray(py)))
+                .and_then(|result| result.extract::<f32>(py))
+                .unwrap_or_else(|err| {
+                    self.record_error(err);
+                    f32::NAN
+                })
+        })
+    }
+
+    fn matrix_dist(&self, a: &Vector, b: &Matrix) -> Vector {
+        Python::with_gil(|py| {
+            let a_array = a.to_pyarray(py);
+
+            if self.vectorized {
+                let results = self
+                    .func
+                    .call1(py, (a_array, b.to_pyarray(py)))
+                    .and_then(|result| result.extract::<Vec<f32>>(py))
+                    .and_then(|results| {
+                        if results.len() == b.nrows() {
+                            Ok(Vector::from(results))
+                        } else {
+                            Err(PyValueError::new_err(format!(
+                                "distance returned {} values for {} rows",
+                                results.len(),
+                                b.nrows()
+                            )))
+                        }
+                    });
+
+                return results.unwrap_or_else(|err| {
+                    self.record_error(err);
+                    Vector::from_elem(b.nrows(), f32::NAN)
+                });
+            }
+
+            b.axis_iter(Axis(0))
+                .map(|row| {
+                    self.func
+                        .call1(py, (a_array, row.to_pyarray(py)))
+                        .and_then(|result| result.extract::<f32>(py))
+                        .unwrap_or_else(|err| {
+                            self.record_error(err);
+                            f32::NAN
+                        })
+                })
+                .collect()
+        })
+    }
+}
+
+/// Flat index with a user-defined distance written in Python
+///
+/// `higher_is_closer` should be set for similarities such as inner product.
+/// See `PyDistance` for the calling convention and performance caveats.
+#[pyclass(name = "IndexFlatCustom")]
+pub struct PyIndexFlatCustom {
+    index: IndexFlatCustom<PyDistance>,
+}
+
+#[pymethods]
+impl PyIndexFlatCustom {
+    #[new]
+    #[args(higher_is_closer = "false", vectorized = "false", chunking = "true")]
+    fn new(
+        dim: usize,
+        distance: PyObject,
+        higher_is_closer: bool,
+        vectorized: bool,
+        chunking: bool,
+    ) -> PyIndexFlatCustom {
+        let distance = PyDistance::new(distance, vectorized);
+        PyIndexFlatCustom {
+            index: IndexFlatCustom::new(dim, chunking, distance, !higher_is_closer),
+        }
+    }
+
+    fn insert(&mut self, vector: PyReadonlyArray1<f32>) -> PyResult<()> {
+        let vector = to_vector(vector);
+        check_dim(&vector, self.index.table.dim)?;
+        self.index.insert(&vector);
+        Ok(())
+    }
+
+    fn insert_many(&mut self, vectors: PyReadonlyArray2<f32>) -> PyResult<()> {
+        let vectors = to_vectors(vectors);
+        for vector in &vectors {
+            check_dim(vector, self.index.table.dim)?;
+        }
+        self.index.insert_many(&vectors);
+        Ok(())
+    }
+
+    fn query(&mut self, vector: PyReadonlyArray1<f32>, k: usize) -> PyResult<QueryResults> {
+        let vector = to_vector(vector);
+        check_dim(&vector, self.index.table.dim)?;
+
+        let results = self.index.matrix_query(&vector, k);
+        self.index.distance().take_error()?;
+        Ok(to_results(results))
+    }
+
+    #[args(limit = "None")]
+    fn range_query(
+        &self,
+        vector: PyReadonlyArray1<f32>,
+        radius: f32,
+        limit: Option<usize>,
+    ) -> PyResult<QueryResults> {
+        let vector = to_vector(vector);
+        check_dim(&vector, self.index.table.dim)?;
+
+        let results = self.index.range_query(&vector, radius, None, limit);
+        self.index.distance().take_error()?;
+        Ok(to_results(results))
+    }
+
+    #[doc = query_mmr_doc!()]
+    #[args(fetch_k = "20", lambda_mult = "0.5")]
+    fn query_mmr(
+        &self,
+        vector: PyReadonlyArray1<f32>,
+        k: usize,
+        fetch_k: usize,
+        lambda_mult: f32,
+    ) -> PyResult<QueryResults> {
+        let vector = to_vector(vector);
+        check_dim(&vector, self.index.table.dim)?;
+
+        let results = self.index.query_mmr(&vector, k, fetch_k, lambda_mult);
+        self.index.distance().take_error()?;
+        Ok(to_results(results))
+    }
+}
+
+macro_rules! py_flat_index {
+    ($py_name:ident, $name:literal, $index:ident) => {
+        #[pyclass(name = $name)]
+        pub struct $py_name {
+            index: $index,
+        }
+
+        #[pymethods]
+        impl $py_name {
+            #[new]
+            #[args(chunking = "true")]
+            fn new(dim: usize, chunking: bool) -> $py_name {
+                $py_name {
+                    index: $index::new(dim, chunking),
+                }
+            }
+
+            fn insert(&mut self, vector: PyReadonlyArray1<f32>) -> PyResult<()> {
+                let vector = to_vector(vector);
+                check_dim(&vector, self.index.table.dim)?;
+                self.index.insert(&vector);
+                Ok(())
+            }
+
+            fn insert_many(&mut self, vectors: PyReadonlyArray2<f32>) -> PyResult<()> {
+                let vectors = to_vectors(vectors);
+                for vector in &vectors {
+                    check_dim(vector, self.index.table.dim)?;
+                }
+                self.index.insert_many(&vectors);
+                Ok(())
+            }
+
+            fn query(&mut self, vector: PyReadonlyArray1<f32>, k: usize) -> PyResult<QueryResults> {
+                let vector = to_vector(vector);
+                check_dim(&vector, self.index.table.dim)?;
+                Ok(to_results(self.index.matrix_query(&vector, k)))
+            }
+
+            #[args(limit = "None")]
+            fn range_query(
+                &self,
+                vector: PyReadonlyArray1<f32>,
+                radius: f32,
+                limit: Option<usize>,
+            ) -> PyResult<QueryResults> {
+                let vector = to_vector(vector);
+                check_dim(&vector, self.index.table.dim)?;
+                Ok(to_results(
+                    self.index.range_query(&vector, radius, None, limit),
+                ))
+            }
+
+            #[doc = query_mmr_doc!()]
+            #[args(fetch_k = "20", lambda_mult = "0.5")]
+            fn query_mmr(
+                &self,
+                vector: PyReadonlyArray1<f32>,
+                k: usize,
+                fetch_k: usize,
+                lambda_mult: f32,
+            ) -> PyResult<QueryResults> {
+                let vector = to_vector(vector);
+                check_dim(&vector, self.index.table.dim)?;
+                Ok(to_results(self.index.query_mmr(
+                    &vector,
+                    k,
+                    fetch_k,
+                    lambda_mult,
+                )))
+            }
+        }
+    };
+}
+
+py_flat_index!(PyIndexFlatIP, "IndexFlatIP", IndexFlatIP);
+py_flat_index!(PyIndexFlatL2, "IndexFlatL2", IndexFlatL2);
+py_flat_index!(PyIndexFlatHP, "IndexFlatHP", IndexFlatHP);
+
+#[pymodule]
+fn latus(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyIndexFlatIP>()?;
+    m.add_class::<PyIndexFlatL2>()?;
+    m.add_class::<PyIndexFlatHP>()?;
+    m.add_class::<PyIndexFlatCustom>()?;
+    Ok(())
+}